@@ -1,3 +1,4 @@
+use lotus_client_rs::address::Protocol;
 use lotus_client_rs::blockanalyzer::{Message,iterate_over_blockchain};
 use env_logger;
 
@@ -5,16 +6,21 @@ fn main() {
     env_logger::Builder::from_default_env().format_timestamp(None).init();
     
     let api = lotus_client_rs::api::ApiClient::new("http://lotus1:1234/rpc/v0");
-    assert!(api.check_endpoint_connection());
+    api.check_endpoint_connection().expect("could not connect to lotus api endpoint");
     
-    let on_height = |height:u64,_blocks:&Vec<String>| {
+    let mut on_height = |height:u64,_blocks:&Vec<String>| {
         println!("Tipset height: {}",height);
     };
-    let on_msg = |msg_cid:&str, msg:&Message| {
-        let find_prefix = "t3";
-        if msg.from.starts_with(&find_prefix) || msg.to.starts_with(&find_prefix) {
-            println!("Message {}:\n  From {}\n  To {}\n",msg_cid,msg.from,msg.to);
+    let mut found = 0;
+    let mut on_msg = |msg_cid:&str, msg:&Message| {
+        if let (Some(from), Some(to)) = (&msg.from, &msg.to) {
+            if from.protocol() == Protocol::Bls || to.protocol() == Protocol::Bls {
+                println!("Message {}:\n  From {}\n  To {}\n",msg_cid,from,to);
+                found += 1;
+            }
         }
     };
-    iterate_over_blockchain(0, 50, &api, Some(on_height), None, None, Some(on_msg), None, None);
+    iterate_over_blockchain(0, 50, &api, Some(&mut on_height), None, None, Some(&mut on_msg), None, None, None)
+        .expect("walk failed");
+    println!("Found {} messages",found);
 }
\ No newline at end of file
@@ -0,0 +1,45 @@
+use lotus_client_rs::blockanalyzer::{CancelHandle,FollowMode,MaxTipsetHeight,WalkOptions,follow_blockchain};
+use lotus_client_rs::visitor::ChainVisitor;
+use env_logger;
+
+// Prints each tipset and the new message cids in it, plus any tipsets reverted by a reorg
+struct HeadPrinter;
+
+impl ChainVisitor for HeadPrinter {
+    fn on_tipset_start(&mut self, height: u64, blocks: &Vec<String>) {
+        println!("Tipset height: {} ({} blocks)",height,blocks.len());
+    }
+
+    fn on_message_cid(&mut self, msg_cid: &str) {
+        println!("  New message: {}",msg_cid);
+    }
+
+    fn on_revert_tipset(&mut self, height: u64, _blocks: &Vec<String>) {
+        println!("Reverted tipset at height: {}",height);
+    }
+
+    fn wants_messages(&self) -> bool {
+        false
+    }
+}
+
+fn main() {
+    env_logger::Builder::from_default_env().format_timestamp(None).init();
+
+    let api = lotus_client_rs::api::ApiClient::new("http://lotus1:1234/rpc/v0");
+    api.check_endpoint_connection().expect("could not connect to lotus api endpoint");
+
+    // Stop cleanly (after the current tipset) when enter is pressed
+    let cancel = CancelHandle::new();
+    let cancel_on_enter = cancel.clone();
+    std::thread::spawn(move || {
+        let mut line = String::new();
+        let _ = std::io::stdin().read_line(&mut line);
+        cancel_on_enter.cancel();
+    });
+
+    // Start a few tipsets back from the head, then keep following it
+    let head_height = MaxTipsetHeight::new(&api).max_height;
+    follow_blockchain(head_height.saturating_sub(5), &api, FollowMode::Subscribe, &cancel, &WalkOptions::default(), &mut HeadPrinter)
+        .expect("walk failed");
+}
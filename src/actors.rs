@@ -0,0 +1,227 @@
+use crate::address::Address;
+use crate::cid::{self, Cid, Multihash};
+use std::collections::HashMap;
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// ActorType - the kinds of builtin actor
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// Method 0 of every actor:  a plain transfer of funds, with no params
+pub const METHOD_SEND : u64 = 0;
+pub const METHOD_CONSTRUCTOR : u64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActorType {
+    System,
+    Init,
+    Cron,
+    Account,
+    Power,
+    Miner,
+    Market,
+    PaymentChannel,
+    Multisig,
+    Reward,
+    VerifiedRegistry,
+}
+
+impl ActorType {
+    // The name in the actor's code CID, e.g. "storageminer" in "fil/2/storageminer"
+    pub fn name(self) -> &'static str {
+        match self {
+            ActorType::System => "system",
+            ActorType::Init => "init",
+            ActorType::Cron => "cron",
+            ActorType::Account => "account",
+            ActorType::Power => "storagepower",
+            ActorType::Miner => "storageminer",
+            ActorType::Market => "storagemarket",
+            ActorType::PaymentChannel => "paymentchannel",
+            ActorType::Multisig => "multisig",
+            ActorType::Reward => "reward",
+            ActorType::VerifiedRegistry => "verifiedregistry",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ActorType> {
+        match name {
+            "system" => Some(ActorType::System),
+            "init" => Some(ActorType::Init),
+            "cron" => Some(ActorType::Cron),
+            "account" => Some(ActorType::Account),
+            "storagepower" => Some(ActorType::Power),
+            "storageminer" => Some(ActorType::Miner),
+            "storagemarket" => Some(ActorType::Market),
+            "paymentchannel" => Some(ActorType::PaymentChannel),
+            "multisig" => Some(ActorType::Multisig),
+            "reward" => Some(ActorType::Reward),
+            "verifiedregistry" => Some(ActorType::VerifiedRegistry),
+            _ => None,
+        }
+    }
+
+    // The code CID of this actor in actors `version` 0 or 2-7 (there was no v1):  a raw CID
+    // whose identity multihash is the string "fil/<version>/<name>", with v0 as "fil/1/...".
+    // (From actors v8 on, code CIDs are hashes of the actor bundle, and have to be
+    // registered with MethodRegistry::register_code_cid().)
+    pub fn legacy_code_cid(self, version: u64) -> Cid {
+        let digest = format!("fil/{}/{}", version.max(1), self.name()).into_bytes();
+        Cid::new_v1(cid::codec::RAW, Multihash{ code: cid::multihash_code::IDENTITY, digest })
+    }
+
+    // The singleton actor at one of the well-known ID addresses (f00-f06, and the burnt
+    // funds account f099), if `address` is one
+    pub fn from_singleton_address(address: &Address) -> Option<ActorType> {
+        match address.id()? {
+            0 => Some(ActorType::System),
+            1 => Some(ActorType::Init),
+            2 => Some(ActorType::Reward),
+            3 => Some(ActorType::Cron),
+            4 => Some(ActorType::Power),
+            5 => Some(ActorType::Market),
+            6 => Some(ActorType::VerifiedRegistry),
+            99 => Some(ActorType::Account),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ActorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// The name of method `method` of the builtin actor `actor`
+pub fn builtin_method_name(actor: ActorType, method: u64) -> Option<&'static str> {
+    match method {
+        METHOD_SEND => return Some("Send"),
+        METHOD_CONSTRUCTOR => return Some("Constructor"),
+        _ => {},
+    }
+    let names : &[&'static str] = match actor {
+        ActorType::System => &[],
+        ActorType::Init => &["Exec"],
+        ActorType::Cron => &["EpochTick"],
+        ActorType::Account => &["PubkeyAddress"],
+        ActorType::Power => &["CreateMiner", "UpdateClaimedPower", "EnrollCronEvent", "OnEpochTickEnd",
+            "UpdatePledgeTotal", "OnConsensusFault", "SubmitPoRepForBulkVerify", "CurrentTotalPower"],
+        ActorType::Miner => &["ControlAddresses", "ChangeWorkerAddress", "ChangePeerID", "SubmitWindowedPoSt",
+            "PreCommitSector", "ProveCommitSector", "ExtendSectorExpiration", "TerminateSectors", "DeclareFaults",
+            "DeclareFaultsRecovered", "OnDeferredCronEvent", "CheckSectorProven", "ApplyRewards",
+            "ReportConsensusFault", "WithdrawBalance", "ConfirmSectorProofsValid", "ChangeMultiaddrs",
+            "CompactPartitions", "CompactSectorNumbers", "ConfirmUpdateWorkerKey", "RepayDebt",
+            "ChangeOwnerAddress", "DisputeWindowedPoSt", "PreCommitSectorBatch", "ProveCommitAggregate",
+            "ProveReplicaUpdates"],
+        ActorType::Market => &["AddBalance", "WithdrawBalance", "PublishStorageDeals", "VerifyDealsForActivation",
+            "ActivateDeals", "OnMinerSectorsTerminate", "ComputeDataCommitment", "CronTick"],
+        ActorType::PaymentChannel => &["UpdateChannelState", "Settle", "Collect"],
+        ActorType::Multisig => &["Propose", "Approve", "Cancel", "AddSigner", "RemoveSigner", "SwapSigner",
+            "ChangeNumApprovalsThreshold", "LockBalance"],
+        ActorType::Reward => &["AwardBlockReward", "ThisEpochReward", "UpdateNetworkKPI"],
+        ActorType::VerifiedRegistry => &["AddVerifier", "RemoveVerifier", "AddVerifiedClient", "UseBytes",
+            "RestoreBytes", "RemoveVerifiedClientDataCap"],
+    };
+    // the actor-specific methods are numbered from 2
+    names.get((method as usize).checked_sub(2)?).copied()
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// MethodRegistry
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// Maps actor code CIDs to actor types, and (actor type, method number) to method names.
+// Knows the builtin actors' methods and their pre-v8 code CIDs out of the box; add the
+// code CIDs of the network's current actor bundle (from Filecoin.StateActorCodeCIDs) with
+// register_code_cid(), and any methods it lacks with register_method().
+#[derive(Debug, Clone, Default)]
+pub struct MethodRegistry {
+    code_cids: HashMap<Cid, ActorType>,
+    methods: HashMap<(ActorType, u64), String>,
+}
+
+impl MethodRegistry {
+    pub fn new() -> MethodRegistry {
+        MethodRegistry::default()
+    }
+
+    pub fn register_code_cid(&mut self, code: Cid, actor: ActorType) {
+        self.code_cids.insert(code, actor);
+    }
+
+    // Adds or renames a method; takes precedence over the builtin names
+    pub fn register_method(&mut self, actor: ActorType, method: u64, name: &str) {
+        self.methods.insert((actor, method), name.to_string());
+    }
+
+    pub fn actor_type(&self, code: &Cid) -> Option<ActorType> {
+        if let Some(actor) = self.code_cids.get(code) {
+            return Some(*actor);
+        }
+        if code.hash().code != cid::multihash_code::IDENTITY {
+            return None;
+        }
+        // "fil/<version>/<name>"
+        let legacy_name = std::str::from_utf8(&code.hash().digest).ok()?;
+        let mut parts = legacy_name.splitn(3, '/');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("fil"), Some(_), Some(name)) => ActorType::from_name(name),
+            _ => None,
+        }
+    }
+
+    pub fn method_name(&self, actor: ActorType, method: u64) -> Option<&str> {
+        match self.methods.get(&(actor, method)) {
+            Some(name) => Some(name),
+            None => builtin_method_name(actor, method),
+        }
+    }
+
+    // The name of method `method` of the actor with code CID `code` (as Filecoin.StateGetActor
+    // reports it)
+    pub fn method_name_for_code(&self, code: &Cid, method: u64) -> Option<&str> {
+        match method {
+            METHOD_SEND => Some("Send"),
+            _ => self.method_name(self.actor_type(code)?, method),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_method_registry() {
+        let mut registry = MethodRegistry::new();
+        // "fil/2/storageminer"
+        let miner_code = ActorType::Miner.legacy_code_cid(2);
+        assert_eq!(miner_code.to_string(), "bafkqaetgnfwc6mrpon2g64tbm5sw22lomvza");
+        assert_eq!(ActorType::Miner.legacy_code_cid(0).hash().digest, b"fil/1/storageminer".to_vec());
+        assert_eq!(ActorType::Miner.legacy_code_cid(7).hash().digest, b"fil/7/storageminer".to_vec());
+        assert_eq!(registry.actor_type(&miner_code), Some(ActorType::Miner));
+        assert_eq!(registry.method_name_for_code(&miner_code, 5), Some("SubmitWindowedPoSt"));
+        assert_eq!(registry.method_name_for_code(&miner_code, 6), Some("PreCommitSector"));
+        assert_eq!(registry.method_name(ActorType::Market, 2), Some("AddBalance"));
+        assert_eq!(registry.method_name(ActorType::Market, 4), Some("PublishStorageDeals"));
+        assert_eq!(registry.method_name(ActorType::System, 2), None);
+        assert_eq!(registry.method_name(ActorType::Account, 0), Some("Send"));
+
+        // a bundle code CID means nothing until registered
+        let bundle_code : Cid = "bafk2bzacecgnynvd3tene3bvqoknuspit56canij5bpra6wl4mrq2mxxwriyu".parse().unwrap();
+        assert_eq!(registry.method_name_for_code(&bundle_code, 4), None);
+        assert_eq!(registry.method_name_for_code(&bundle_code, 0), Some("Send"));
+        registry.register_code_cid(bundle_code.clone(), ActorType::Market);
+        assert_eq!(registry.method_name_for_code(&bundle_code, 4), Some("PublishStorageDeals"));
+
+        registry.register_method(ActorType::Market, 10, "SettleDealPayments");
+        assert_eq!(registry.method_name(ActorType::Market, 10), Some("SettleDealPayments"));
+
+        assert_eq!(ActorType::from_singleton_address(&Address::new_id(5)), Some(ActorType::Market));
+        assert_eq!(ActorType::from_singleton_address(&Address::new_id(1000)), None);
+    }
+}
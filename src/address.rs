@@ -0,0 +1,363 @@
+use crate::error::AddressError;
+use crate::varint;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// Network / Protocol
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// Which network an address's string form is for:  it starts with `f` on mainnet and `t`
+// on the testnets.  The byte form doesn't say, so addresses decoded from bytes are mainnet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+}
+
+impl Default for Network {
+    fn default() -> Network {
+        Network::Mainnet
+    }
+}
+
+impl Network {
+    pub fn prefix(&self) -> char {
+        match self {
+            Network::Mainnet => 'f',
+            Network::Testnet => 't',
+        }
+    }
+
+    pub fn from_prefix(prefix: char) -> Option<Network> {
+        match prefix {
+            'f' => Some(Network::Mainnet),
+            't' => Some(Network::Testnet),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    // An actor id, e.g. f01024
+    Id = 0,
+    // The blake2b-160 hash of a secp256k1 public key
+    Secp256k1 = 1,
+    // The blake2b-160 hash of the data an actor was created from
+    Actor = 2,
+    // A BLS public key
+    Bls = 3,
+    // An address in the namespace of an address manager actor, e.g. f410f... for the EAM
+    Delegated = 4,
+}
+
+impl Protocol {
+    pub fn from_byte(byte: u8) -> Option<Protocol> {
+        match byte {
+            0 => Some(Protocol::Id),
+            1 => Some(Protocol::Secp256k1),
+            2 => Some(Protocol::Actor),
+            3 => Some(Protocol::Bls),
+            4 => Some(Protocol::Delegated),
+            _ => None,
+        }
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// Address
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+const HASH_PAYLOAD_LEN : usize = 20;
+const BLS_PAYLOAD_LEN : usize = 48;
+const MAX_SUBADDRESS_LEN : usize = 54;
+const CHECKSUM_LEN : usize = 4;
+
+// A Filecoin address.  Parse one from its string form (f01024, t3wzcp...) with parse(),
+// or from the byte form used in CBOR with from_bytes(); to_string() and to_bytes() go the
+// other way.  Two addresses are equal if they have the same protocol and payload, whatever
+// their network.
+//
+// In json (as Lotus sends it) an Address is its string form.
+#[derive(Debug, Clone)]
+pub struct Address {
+    network: Network,
+    protocol: Protocol,
+    // as in the byte form:  a uvarint for Id, the namespace's uvarint followed by the
+    // subaddress for Delegated
+    payload: Vec<u8>,
+}
+
+impl Address {
+    pub fn new_id(id: u64) -> Address {
+        Address{ network: Network::default(), protocol: Protocol::Id, payload: varint::encode(id) }
+    }
+
+    // From the 20 byte blake2b-160 hash of a public key
+    pub fn new_secp256k1(pubkey_hash: &[u8]) -> Result<Address, AddressError> {
+        Address::from_payload(Protocol::Secp256k1, pubkey_hash.to_vec())
+    }
+
+    pub fn new_actor(actor_hash: &[u8]) -> Result<Address, AddressError> {
+        Address::from_payload(Protocol::Actor, actor_hash.to_vec())
+    }
+
+    // From a 48 byte BLS public key
+    pub fn new_bls(pubkey: &[u8]) -> Result<Address, AddressError> {
+        Address::from_payload(Protocol::Bls, pubkey.to_vec())
+    }
+
+    pub fn new_delegated(namespace: u64, subaddress: &[u8]) -> Result<Address, AddressError> {
+        let mut payload = varint::encode(namespace);
+        payload.extend_from_slice(subaddress);
+        Address::from_payload(Protocol::Delegated, payload)
+    }
+
+    // Validates `payload` as the payload of a `protocol` address
+    fn from_payload(protocol: Protocol, payload: Vec<u8>) -> Result<Address, AddressError> {
+        let valid = match protocol {
+            Protocol::Id => varint::decode(&payload).map(|(_, len)| len == payload.len()).unwrap_or(false),
+            Protocol::Secp256k1 | Protocol::Actor => payload.len() == HASH_PAYLOAD_LEN,
+            Protocol::Bls => payload.len() == BLS_PAYLOAD_LEN,
+            Protocol::Delegated => varint::decode(&payload)
+                .map(|(_, len)| payload.len() - len <= MAX_SUBADDRESS_LEN)
+                .unwrap_or(false),
+        };
+        if !valid {
+            return Err(AddressError::InvalidPayload(format!("{} bytes for protocol {}", payload.len(), protocol as u8)));
+        }
+        Ok(Address{ network: Network::default(), protocol, payload })
+    }
+
+    // From the byte form:  the protocol byte followed by the payload
+    pub fn from_bytes(bytes: &[u8]) -> Result<Address, AddressError> {
+        let (protocol_byte, payload) = match bytes.split_first() {
+            Some(split) => split,
+            None => return Err(AddressError::InvalidPayload("empty address".to_string())),
+        };
+        let protocol = Protocol::from_byte(*protocol_byte)
+            .ok_or_else(|| AddressError::UnknownProtocol(protocol_byte.to_string()))?;
+        Address::from_payload(protocol, payload.to_vec())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(1 + self.payload.len());
+        bytes.push(self.protocol as u8);
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    // The same address, written for `network`
+    pub fn with_network(mut self, network: Network) -> Address {
+        self.network = network;
+        self
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    // The actor id of an Id address
+    pub fn id(&self) -> Option<u64> {
+        match self.protocol {
+            Protocol::Id => varint::decode(&self.payload).map(|(id, _)| id),
+            _ => None,
+        }
+    }
+
+    // The namespace and subaddress of a Delegated address
+    pub fn delegated(&self) -> Option<(u64, &[u8])> {
+        match self.protocol {
+            Protocol::Delegated => varint::decode(&self.payload).map(|(namespace, len)| (namespace, &self.payload[len..])),
+            _ => None,
+        }
+    }
+
+    fn checksum(&self) -> Vec<u8> {
+        blake2b_simd::Params::new()
+            .hash_length(CHECKSUM_LEN)
+            .hash(&self.to_bytes())
+            .as_bytes()
+            .to_vec()
+    }
+}
+
+impl PartialEq for Address {
+    fn eq(&self, other: &Address) -> bool {
+        self.protocol == other.protocol && self.payload == other.payload
+    }
+}
+
+impl Eq for Address {}
+
+impl std::hash::Hash for Address {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.protocol.hash(state);
+        self.payload.hash(state);
+    }
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}", self.network.prefix(), self.protocol as u8)?;
+        let mut checksummed = match self.protocol {
+            Protocol::Id => return write!(f, "{}", self.id().unwrap_or(0)),
+            Protocol::Delegated => {
+                let (namespace, subaddress) = self.delegated().unwrap_or((0, &[]));
+                write!(f, "{}f", namespace)?;
+                subaddress.to_vec()
+            },
+            _ => self.payload.clone(),
+        };
+        checksummed.extend_from_slice(&self.checksum());
+        write!(f, "{}", base32_encode(&checksummed))
+    }
+}
+
+impl std::str::FromStr for Address {
+    type Err = AddressError;
+
+    // Parses the string form, verifying its checksum
+    fn from_str(s: &str) -> Result<Address, AddressError> {
+        let invalid = || AddressError::InvalidPayload(s.to_string());
+        let mut chars = s.chars();
+        let network = chars.next()
+            .and_then(Network::from_prefix)
+            .ok_or_else(|| AddressError::UnknownNetwork(s.to_string()))?;
+        let protocol = chars.next()
+            .and_then(|c| c.to_digit(10))
+            .and_then(|digit| Protocol::from_byte(digit as u8))
+            .ok_or_else(|| AddressError::UnknownProtocol(s.to_string()))?;
+        let raw = chars.as_str();
+
+        let (address, checksum) = match protocol {
+            Protocol::Id => {
+                if raw.is_empty() || raw.len() > 20 || !raw.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(invalid());
+                }
+                let id = raw.parse::<u64>().map_err(|_| invalid())?;
+                return Ok(Address::new_id(id).with_network(network));
+            },
+            Protocol::Delegated => {
+                let (namespace, encoded) = raw.split_at(raw.find('f').ok_or_else(invalid)?);
+                if namespace.is_empty() || namespace.len() > 20 || !namespace.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(invalid());
+                }
+                let namespace = namespace.parse::<u64>().map_err(|_| invalid())?;
+                let (subaddress, checksum) = base32_decode_checksummed(&encoded[1..]).ok_or_else(invalid)?;
+                (Address::new_delegated(namespace, &subaddress)?, checksum)
+            },
+            _ => {
+                let (payload, checksum) = base32_decode_checksummed(raw).ok_or_else(invalid)?;
+                (Address::from_payload(protocol, payload)?, checksum)
+            },
+        };
+        if address.checksum() != checksum {
+            return Err(AddressError::InvalidChecksum(s.to_string()));
+        }
+        Ok(address.with_network(network))
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Address, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    base32::encode(base32::Alphabet::RFC4648{ padding: false }, bytes).to_ascii_lowercase()
+}
+
+// Splits the decoded bytes into the payload and the checksum after it.  Only lowercase is
+// accepted, as Lotus writes it.
+fn base32_decode_checksummed(s: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+    if !s.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()) {
+        return None;
+    }
+    let mut bytes = base32::decode(base32::Alphabet::RFC4648{ padding: false }, &s.to_ascii_uppercase())?;
+    if bytes.len() < CHECKSUM_LEN {
+        return None;
+    }
+    let checksum = bytes.split_off(bytes.len() - CHECKSUM_LEN);
+    Some((bytes, checksum))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_id_address() {
+        let address : Address = "f01024".parse().unwrap();
+        assert_eq!(address.protocol(), Protocol::Id);
+        assert_eq!(address.id(), Some(1024));
+        assert_eq!(address.to_bytes(), vec![0x00, 0x80, 0x08]);
+        assert_eq!(Address::from_bytes(&[0x00, 0x80, 0x08]).unwrap(), address);
+        assert_eq!(address.to_string(), "f01024");
+
+        let testnet : Address = "t01024".parse().unwrap();
+        assert_eq!(testnet.network(), Network::Testnet);
+        assert_eq!(testnet, address, "the network isn't part of the address");
+        assert_eq!(testnet.to_string(), "t01024");
+        assert_eq!(Address::new_id(0).to_string(), "f00");
+
+        assert!("f0".parse::<Address>().is_err());
+        assert!("f0-1".parse::<Address>().is_err());
+        assert!(Address::from_bytes(&[0x00, 0x80, 0x00]).is_err(), "not minimally encoded");
+    }
+
+    #[test]
+    fn test_hash_addresses() {
+        // from a BLS message on the testnet
+        let s = "t3wzcpwznw6dvl6x3beekspluvhdwh26h3tvmw5y2fychse7pr6xlsfmxuhsv6ki7r3pm6s7gxc65h52lgqfsa";
+        let address : Address = s.parse().unwrap();
+        assert_eq!(address.protocol(), Protocol::Bls);
+        assert_eq!(address.payload().len(), 48);
+        assert_eq!(address.to_string(), s);
+        assert_eq!(Address::from_bytes(&address.to_bytes()).unwrap().with_network(Network::Testnet).to_string(), s);
+
+        // a changed character breaks the checksum
+        let bad = s.replace("wzcp", "wzcq");
+        assert!(matches!(bad.parse::<Address>(), Err(AddressError::InvalidChecksum(_))));
+        assert!(matches!(s.to_uppercase().parse::<Address>(), Err(AddressError::UnknownNetwork(_))));
+        assert!(matches!("x01".parse::<Address>(), Err(AddressError::UnknownNetwork(_))));
+        assert!(matches!("f91".parse::<Address>(), Err(AddressError::UnknownProtocol(_))));
+
+        let secp = Address::new_secp256k1(&[7u8; 20]).unwrap();
+        assert_eq!(secp.to_string().parse::<Address>().unwrap(), secp);
+        assert!(Address::new_secp256k1(&[7u8; 19]).is_err());
+    }
+
+    #[test]
+    fn test_delegated_address() {
+        let subaddress = [0x52, 0x96, 0x3e, 0xf5, 0x0e, 0x27, 0xe0, 0x6d, 0x72, 0xd5,
+                          0x9f, 0xcb, 0x4f, 0x3c, 0x2a, 0x68, 0x7b, 0xe3, 0xcf, 0xef];
+        let address = Address::new_delegated(10, &subaddress).unwrap();
+        let s = address.to_string();
+        assert_eq!(s, "f410fkkld55ioe7qg24wvt7fu6pbknb56ht7pt4zamxa");
+        let parsed : Address = s.parse().unwrap();
+        assert_eq!(parsed, address);
+        assert_eq!(parsed.delegated(), Some((10, &subaddress[..])));
+        assert_eq!(address.to_bytes()[..2], [0x04, 0x0a]);
+    }
+}
@@ -3,6 +3,15 @@
 
 use serde_json::json;
 use log;
+use crate::address::Address;
+use crate::cid::Cid;
+use crate::error::LotusError;
+use crate::tokenamount::TokenAmount;
+use crate::types;
+use crate::ws;
+
+// Default time to wait for the node to answer a single request
+const DEFAULT_TIMEOUT : std::time::Duration = std::time::Duration::from_secs(60);
 
 //////////////////////////////////////////////////////////////////////////////////////
 //
@@ -11,11 +20,21 @@ use log;
 //////////////////////////////////////////////////////////////////////////////////////
 
 macro_rules! make_api_function {
-    ($selfparam:ident, $method_call_name:literal, $auth_token_value:expr, $expr_evals_to_params:expr) => {
-        async_std::task::block_on(async move {
-            let transport = jsonrpsee::transport::http::HttpTransportClient::new(&$selfparam.endpoint_url, $auth_token_value);
-            let mut raw_client = jsonrpsee::raw::RawClient::new(transport);
-            match raw_client.start_request($method_call_name, $expr_evals_to_params).await {
+    ($selfparam:ident, $method_call_name:literal, $expr_evals_to_params:expr) => {{
+        let request = async {
+            // Calls take an idle RawClient (and so an HttpTransportClient with its keep-alive
+            // connection) from the client's pool, or build one if all are busy, and put it
+            // back when done.  The pool is only locked while taking or returning one, so
+            // concurrent calls run in parallel.
+            let idle_raw_client = $selfparam.raw_client.lock().await.pop();
+            let mut raw_client = match idle_raw_client {
+                Some(raw_client) => raw_client,
+                None => {
+                    let transport = jsonrpsee::transport::http::HttpTransportClient::new(&$selfparam.endpoint_url, $selfparam.auth_token_str());
+                    jsonrpsee::raw::RawClient::new(transport)
+                }
+            };
+            let result : Result<jsonrpsee::common::JsonValue, LotusError> = match raw_client.start_request($method_call_name, $expr_evals_to_params).await {
                 Ok(request_id) => {
                     match raw_client.request_by_id(request_id) {
                         Some(fut) => {
@@ -23,41 +42,55 @@ macro_rules! make_api_function {
                                 Ok(jsonval) => {
                                     match jsonrpsee::common::from_value::<jsonrpsee::common::JsonValue>(jsonval) {
                                         Ok(ret) => {
-                                            ret
+                                            Ok(ret)
                                         },
                                         Err(e) => {
                                             log::error!("make_api_function!: fut.await result was Ok() but failed to parse to json:  error: {}",e);
-                                            jsonrpsee::common::JsonValue::Null
+                                            Err(LotusError::from(e))
                                         }
                                     }
                                 },
                                 Err(e) => {
                                     log::error!("make_api_function!: fut.await result was error type: {}",e);
-                                    jsonrpsee::common::JsonValue::Null
+                                    Err(LotusError::from(e))
                                 }
                             }
                         },
                         None => {
                             log::error!("make_api_function!: raw_client.request_by_id(request_id='{:?}') was None",request_id);
-                            jsonrpsee::common::JsonValue::Null
+                            Err(LotusError::Transport(format!("no pending request with id {:?}",request_id)))
                         }
                     }
                 },
                 Err(e) => {
                     log::error!("make_api_function!: 'raw_client.start_request($method_call_name, $expr_evals_to_params).await': error: {}",e);
-                    jsonrpsee::common::JsonValue::Null
+                    Err(LotusError::Transport(e.to_string()))
                 }
+            };
+            // Don't keep reusing a connection that just failed
+            if !matches!(&result, Err(LotusError::Transport(_))) {
+                $selfparam.raw_client.lock().await.push(raw_client);
             }
-        })
-    }
+            result
+        };
+        match async_std::future::timeout($selfparam.timeout, request).await {
+            Ok(result) => result,
+            Err(_) => {
+                log::error!("make_api_function!: {} timed out after {:?}",$method_call_name,$selfparam.timeout);
+                // The RawClient of the abandoned request was dropped with it, so it doesn't go
+                // back in the pool
+                Err(LotusError::Timeout($selfparam.timeout))
+            }
+        }
+    }}
 }
 
 //
-// Example of using make_api_function! macro to make an api calling function:
+// Example of using make_api_function! macro to make an (async) api calling function:
 //
-// pub fn chain_get_tipset_by_height(height: u64) -> jsonrpsee::common::JsonValue {
-//     make_api_function!("Filecoin.ChainGetTipSetByHeight",                # Specify API method name
-//                        "",                                               # Optionally provide auth token
+// pub async fn chain_get_tipset_by_height(&self, height: u64) -> Result<jsonrpsee::common::JsonValue, LotusError> {
+//     make_api_function!(self,                                             # The ApiClient (endpoint, token, timeout)
+//                        "Filecoin.ChainGetTipSetByHeight",                # Specify API method name
 //     {            
 //         let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();   # In this block, build
 //         v_params.push(json!(height));                                    # an object that mimics the
@@ -67,32 +100,251 @@ macro_rules! make_api_function {
 //     })
 // }
 //
+// The client's auth token (if any) is sent by the transport as `Authorization: Bearer <token>`
+// on every request, so methods needing 'write' or 'admin' permission work too.
+//
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// Endpoint helpers - turn Lotus multiaddrs into RPC urls
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+const FULLNODE_API_INFO : &str = "FULLNODE_API_INFO";
+const DEFAULT_LISTEN_ADDRESS : &str = "/ip4/127.0.0.1/tcp/1234/http";
+
+// Converts a Lotus API multiaddr into the url of its JSON RPC endpoint:
+//
+//      /ip4/10.0.0.5/tcp/1234/http     =>  http://10.0.0.5:1234/rpc/v0
+//      /dns/lotus1/tcp/1234/http       =>  http://lotus1:1234/rpc/v0
+//      /ip6/::1/tcp/1234/http          =>  http://[::1]:1234/rpc/v0
+//      /ip4/10.0.0.5/tcp/1234/ws       =>  http://10.0.0.5:1234/rpc/v0
+//
+// An unspecified listen address (0.0.0.0 or ::) is dialed as localhost.
+pub fn multiaddr_to_endpoint_url(multiaddr: &str) -> Result<String, LotusError> {
+    let bad_multiaddr = |why: &str| LotusError::Config(format!("unsupported multiaddr '{}': {}",multiaddr,why));
 
+    let mut host : Option<String> = None;
+    let mut port : Option<u16> = None;
+    let mut scheme = "http";
+    let mut parts = multiaddr.trim().split('/').filter(|part| *part != "");
+    while let Some(protocol) = parts.next() {
+        match protocol {
+            "ip4" | "ip6" | "dns" | "dns4" | "dns6" => {
+                let addr = parts.next().ok_or_else(|| bad_multiaddr("missing address"))?;
+                host = Some(match (protocol, addr) {
+                    ("ip4", "0.0.0.0") => "127.0.0.1".to_string(),
+                    ("ip6", "::") => "[::1]".to_string(),
+                    ("ip6", _) => format!("[{}]",addr),
+                    _ => addr.to_string(),
+                });
+            },
+            "tcp" => {
+                let port_str = parts.next().ok_or_else(|| bad_multiaddr("missing tcp port"))?;
+                port = Some(port_str.parse::<u16>().map_err(|_| bad_multiaddr("bad tcp port"))?);
+            },
+            // The endpoint is for http calls; a node listening for websockets takes those
+            // on the same port, and ws::endpoint_url_to_ws_url() gives the websocket url
+            "http" | "ws" => scheme = "http",
+            "https" | "wss" => scheme = "https",
+            _ => return Err(bad_multiaddr(&format!("unknown protocol '{}'",protocol))),
+        }
+    }
+
+    match (host, port) {
+        (Some(host), Some(port)) => Ok(format!("{}://{}:{}/rpc/v0",scheme,host,port)),
+        (None, _) => Err(bad_multiaddr("no ip4/ip6/dns address")),
+        (_, None) => Err(bad_multiaddr("no tcp port")),
+    }
+}
+
+// Expands a leading `~` to $HOME, so `~/.lotus` can be passed as a repo path.
+fn expand_home_dir(path: &std::path::Path) -> std::path::PathBuf {
+    if let Ok(rest) = path.strip_prefix("~") {
+        if let Ok(home) = std::env::var("HOME") {
+            return std::path::PathBuf::from(home).join(rest);
+        }
+    }
+    path.to_path_buf()
+}
+
+#[derive(Clone)]
 enum AuthToken {
     None,
     Value(String),
 }
 
-pub struct ApiClient {
+// The idle transports of a client (see make_api_function!)
+type SharedRawClient = std::sync::Arc<async_std::sync::Mutex<Vec<
+    jsonrpsee::raw::RawClient<jsonrpsee::transport::http::HttpTransportClient>>>>;
+
+fn new_shared_raw_client() -> SharedRawClient {
+    std::sync::Arc::new(async_std::sync::Mutex::new(Vec::new()))
+}
+
+// Websocket connection for channel methods (ChainNotify, ...), opened on first use
+type SharedWsClient = std::sync::Arc<async_std::sync::Mutex<Option<ws::WsClient>>>;
+
+fn new_shared_ws_client() -> SharedWsClient {
+    std::sync::Arc::new(async_std::sync::Mutex::new(None))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(jsonval: jsonrpsee::common::JsonValue) -> Result<T, LotusError> {
+    Ok(serde_json::from_value(jsonval)?)
+}
+
+// For list results, which Lotus returns as `null` rather than `[]` when empty
+fn from_json_or_default<T: serde::de::DeserializeOwned + Default>(jsonval: jsonrpsee::common::JsonValue) -> Result<T, LotusError> {
+    if jsonval.is_null() {
+        Ok(T::default())
+    } else {
+        from_json(jsonval)
+    }
+}
+
+// The tipset a state query is evaluated at.  Lotus takes an empty key to mean the head.
+fn tipset_key_param(tipset_key: Option<&types::TipSetKey>) -> jsonrpsee::common::JsonValue {
+    match tipset_key {
+        Some(tipset_key) => json!(tipset_key),
+        None => jsonrpsee::common::JsonValue::Array(vec!()),
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// AsyncApiClient - the async-first client every api method is implemented on
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// A pool of long-lived transports is shared by all calls (and by all clones of the client),
+// so connections to the node are kept alive and reused instead of being set up per request.
+// Each call in flight has a transport of its own, so concurrent calls on one client (from
+// several tasks, or joined futures) run in parallel; the pool grows to the most calls made
+// at once.
+#[derive(Clone)]
+pub struct AsyncApiClient {
     endpoint_url: String,
     auth_token: AuthToken,
+    timeout: std::time::Duration,
+    raw_client: SharedRawClient,
+    ws_client: SharedWsClient,
 }
 
-impl ApiClient {
-    pub fn new(endpoint_url: &str) -> ApiClient {
-        ApiClient{
+impl AsyncApiClient {
+    pub fn new(endpoint_url: &str) -> AsyncApiClient {
+        AsyncApiClient{
             endpoint_url : endpoint_url.to_string(),
             auth_token : AuthToken::None,
+            timeout : DEFAULT_TIMEOUT,
+            raw_client : new_shared_raw_client(),
+            ws_client : new_shared_ws_client(),
+        }
+    }
+
+    // Builds a client from the string Lotus prints for `lotus auth api-info --perm admin`
+    // and expects in the FULLNODE_API_INFO environment variable, i.e. either
+    // `<token>:<multiaddr>` or just `<multiaddr>`, for example:
+    //
+    //      eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9...:/ip4/127.0.0.1/tcp/1234/http
+    //
+    pub fn from_api_info(api_info: &str) -> Result<AsyncApiClient, LotusError> {
+        let api_info = api_info.trim();
+        let (token, multiaddr) = if api_info.starts_with('/') {
+            ("", api_info)
+        } else {
+            let mut parts = api_info.splitn(2, ':');
+            let token = parts.next().unwrap_or("");
+            match parts.next() {
+                Some(multiaddr) => (token, multiaddr),
+                None => return Err(LotusError::Config(format!("api info '{}' is not of the form <token>:<multiaddr>",api_info))),
+            }
+        };
+        let mut api = AsyncApiClient::new(&multiaddr_to_endpoint_url(multiaddr)?);
+        if token != "" {
+            api.auth_token(token);
+        }
+        Ok(api)
+    }
+
+    // Same as from_api_info() but reads the FULLNODE_API_INFO environment variable.
+    pub fn from_env() -> Result<AsyncApiClient, LotusError> {
+        match std::env::var(FULLNODE_API_INFO) {
+            Ok(api_info) => AsyncApiClient::from_api_info(&api_info),
+            Err(e) => Err(LotusError::Config(format!("{}: {}",FULLNODE_API_INFO,e))),
+        }
+    }
+
+    // Builds a client from a Lotus repo directory (usually `~/.lotus`), taking the token from
+    // `<repo>/token` and the listen address from `[API] ListenAddress` in `<repo>/config.toml`.
+    // If config.toml leaves ListenAddress commented out, the address the running daemon wrote
+    // to `<repo>/api` is used, and failing that Lotus's default `/ip4/127.0.0.1/tcp/1234/http`.
+    pub fn from_repo_path<P: AsRef<std::path::Path>>(repo_path: P) -> Result<AsyncApiClient, LotusError> {
+        let repo_path = expand_home_dir(repo_path.as_ref());
+
+        let token_path = repo_path.join("token");
+        let token = std::fs::read_to_string(&token_path)
+            .map_err(|e| LotusError::Config(format!("{}: {}",token_path.display(),e)))?;
+
+        let config_path = repo_path.join("config.toml");
+        let mut multiaddr : Option<String> = None;
+        if let Ok(config_str) = std::fs::read_to_string(&config_path) {
+            let config : toml::Value = toml::from_str(&config_str)
+                .map_err(|e| LotusError::Config(format!("{}: {}",config_path.display(),e)))?;
+            if let Some(listen_address) = config.get("API").and_then(|api| api.get("ListenAddress")) {
+                if let Some(s) = listen_address.as_str() {
+                    multiaddr = Some(s.to_string());
+                }
+            }
+        }
+        if multiaddr.is_none() {
+            if let Ok(s) = std::fs::read_to_string(repo_path.join("api")) {
+                multiaddr = Some(s.trim().to_string());
+            }
         }
+        let multiaddr = multiaddr.unwrap_or(DEFAULT_LISTEN_ADDRESS.to_string());
+
+        let mut api = AsyncApiClient::new(&multiaddr_to_endpoint_url(&multiaddr)?);
+        let token = token.trim();
+        if token != "" {
+            api.auth_token(token);
+        }
+        Ok(api)
     }
 
     pub fn auth_token(& mut self, auth_token: &str) {
         assert_ne!(auth_token,"");
         self.auth_token = AuthToken::Value(String::from(auth_token));
+        self.raw_client = new_shared_raw_client();
+        self.ws_client = new_shared_ws_client();
+    }
+
+    fn auth_token_str(&self) -> &str {
+        match &self.auth_token {
+            AuthToken::None => "",
+            AuthToken::Value(s) => s,
+        }
     }
 
     pub fn endpoint(& mut self, endpoint: &str) {
         self.endpoint_url = endpoint.to_string();
+        self.raw_client = new_shared_raw_client();
+        self.ws_client = new_shared_ws_client();
+    }
+
+    // How long to wait for an answer before a call fails with LotusError::Timeout
+    pub fn timeout(& mut self, timeout: std::time::Duration) {
+        self.timeout = timeout;
+    }
+
+    // A client for the same node (same endpoint, token and timeout) with its own pool of
+    // transports and its own websocket, e.g. for calls that shouldn't share keep-alive
+    // connections with this client's, or that change the timeout.
+    pub fn new_connection(&self) -> AsyncApiClient {
+        AsyncApiClient{
+            raw_client : new_shared_raw_client(),
+            ws_client : new_shared_ws_client(),
+            ..self.clone()
+        }
     }
 
     //////////////////////////////////////////////////////////////////////////////////////
@@ -106,8 +358,8 @@ impl ApiClient {
     //      --data '{ "jsonrpc": "2.0", "method": "Filecoin.ChainGetTipSetByHeight", 
     //      "params":[33,[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
     // (yes, the empty array is an essential parameter; it indicates 'types.EmptyTSK')
-    pub fn chain_get_tipset_by_height(&self, height: u64) -> jsonrpsee::common::JsonValue {
-        make_api_function!(self, "Filecoin.ChainGetTipSetByHeight","",{
+    pub async fn chain_get_tipset_by_height(&self, height: u64) -> Result<jsonrpsee::common::JsonValue, LotusError> {
+        make_api_function!(self, "Filecoin.ChainGetTipSetByHeight",{
             let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
             v_params.push(json!(height));
             v_params.push(jsonrpsee::common::JsonValue::Array(vec!()));
@@ -116,6 +368,27 @@ impl ApiClient {
         })
     }
 
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // chain_get_tipset
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // Looks up a tipset by its key (the CIDs of its blocks), e.g. the Parents of a block.
+    // equivalent curl:  curl -X POST -H "Content-Type: application/json" 
+    //      --data '{ "jsonrpc": "2.0", "method": "Filecoin.ChainGetTipSet", 
+    //      "params":[[{"/":"bafy2bzacecwan7xlr3lwroihugoanxctki2tmrrb6inxeqwkmwcyqwo7rcn3e"}]], 
+    //      "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning the same json structure as chain_head.
+    pub async fn chain_get_tipset(&self, tipset_key: &types::TipSetKey) -> Result<jsonrpsee::common::JsonValue, LotusError> {
+        make_api_function!(self, "Filecoin.ChainGetTipSet",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })
+    }
+
     //////////////////////////////////////////////////////////////////////////////////////
     //
     // chain_head
@@ -195,8 +468,8 @@ impl ApiClient {
     //     },
     //     "id": 0
     //   }
-    pub fn chain_head(&self) -> jsonrpsee::common::JsonValue {
-        make_api_function!(self, "Filecoin.ChainHead","",{
+    pub async fn chain_head(&self) -> Result<jsonrpsee::common::JsonValue, LotusError> {
+        make_api_function!(self, "Filecoin.ChainHead",{
             jsonrpsee::common::Params::None
         })
     }
@@ -209,33 +482,30 @@ impl ApiClient {
 
     // Tries to make a simple API query to test the connection.
     //
-    // Returns:  true if the test query was successful, otherwise false.
-    pub fn check_endpoint_connection(&self) -> bool {
+    // Returns:  Ok(()) if the test query was successful, otherwise the LotusError saying
+    // what went wrong (transport failure, rpc error, unparseable response or timeout).
+    pub async fn check_endpoint_connection(&self) -> Result<(), LotusError> {
         log::debug!("check_endpoint_connection: for endpoint '{}'",self.endpoint_url);
 
-        let _height : u64;
-
-        let ret_jsonval : jsonrpsee::common::JsonValue;
-        ret_jsonval = self.chain_head();
-        let ret_jsonval_option = ret_jsonval.pointer("/Height");
-        let height_jsonval : jsonrpsee::common::JsonValue;
-        if let Some(height_jsonval) = &ret_jsonval_option {
+        let ret_jsonval = self.chain_head().await.map_err(|e| {
+            log::debug!("check_endpoint_connection: aborted at chain_head: {}",e);
+            e
+        })?;
+        if let Some(height_jsonval) = ret_jsonval.pointer("/Height") {
             log::info!("check_endpoint_connection: got height_jsonval='{}'",height_jsonval.to_string());
 
-            let height_option : Option<u64> = height_jsonval.as_u64();
-            if let Some(number) = height_option {
-                _height = number;
-                log::info!("check_endpoint_connection: got height='{}'",_height);
+            if let Some(height) = height_jsonval.as_u64() {
+                log::info!("check_endpoint_connection: got height='{}'",height);
             } else {
                 log::debug!("check_endpoint_connection: aborted at u64 unwrap");
-                return false;
+                return Err(LotusError::Deserialize(format!("Height is not a u64: '{}'",height_jsonval)));
             }
         } else {
             log::debug!("check_endpoint_connection: aborted at Height unwrap");
-            return false;
+            return Err(LotusError::Deserialize("ChainHead response has no Height".to_string()));
         }
         log::debug!("check_endpoint_connection: endpoint OK '{}'",self.endpoint_url);
-        true
+        Ok(())
     }
 
     //////////////////////////////////////////////////////////////////////////////////////
@@ -312,8 +582,8 @@ impl ApiClient {
     // Notes:
     // - These are just the block headers and don't contain any of the cids or cid data.
     //
-    pub fn chain_get_block(&self, block_cid: &str) -> jsonrpsee::common::JsonValue {
-        make_api_function!(self, "Filecoin.ChainGetBlock","",{
+    pub async fn chain_get_block(&self, block_cid: &str) -> Result<jsonrpsee::common::JsonValue, LotusError> {
+        make_api_function!(self, "Filecoin.ChainGetBlock",{
             let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
             v_params.push(json!({"/":block_cid}));
             let params = jsonrpsee::common::Params::Array(v_params);
@@ -386,8 +656,8 @@ impl ApiClient {
     // - The order of the CIDs is always this:  1st BLS message, 2nd BLS message, ..., last BLS message, 
     // 1st Secp message, 2nd secp message, ..., last secp message
     // 
-    pub fn chain_get_block_messages(&self, block_cid: &str) -> jsonrpsee::common::JsonValue {
-        make_api_function!(self, "Filecoin.ChainGetBlockMessages","",{
+    pub async fn chain_get_block_messages(&self, block_cid: &str) -> Result<jsonrpsee::common::JsonValue, LotusError> {
+        make_api_function!(self, "Filecoin.ChainGetBlockMessages",{
             let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
             v_params.push(json!({"/":block_cid}));
             let params = jsonrpsee::common::Params::Array(v_params);
@@ -434,8 +704,8 @@ impl ApiClient {
     // where:
     // - There can be any number of Cid,Message pair blocks like above.
     //
-    pub fn chain_get_parent_messages(&self, block_cid: &str) -> jsonrpsee::common::JsonValue {
-        make_api_function!(self, "Filecoin.ChainGetParentMessages","",{
+    pub async fn chain_get_parent_messages(&self, block_cid: &str) -> Result<jsonrpsee::common::JsonValue, LotusError> {
+        make_api_function!(self, "Filecoin.ChainGetParentMessages",{
             let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
             v_params.push(json!({"/":block_cid}));
             let params = jsonrpsee::common::Params::Array(v_params);
@@ -478,12 +748,1141 @@ impl ApiClient {
     // - The order of the receipts here corresponds to the order of the Cid,Message pairs in 
     // the response to Filecoin.ChainGetParentMessages.
     //
-    pub fn chain_get_parent_receipts(&self, block_cid: &str) -> jsonrpsee::common::JsonValue {
-        make_api_function!(self, "Filecoin.ChainGetParentReceipts","",{
+    pub async fn chain_get_parent_receipts(&self, block_cid: &str) -> Result<jsonrpsee::common::JsonValue, LotusError> {
+        make_api_function!(self, "Filecoin.ChainGetParentReceipts",{
             let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
             v_params.push(json!({"/":block_cid}));
             let params = jsonrpsee::common::Params::Array(v_params);
             params
         })
     }
-}
\ No newline at end of file
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // chain_get_message
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // One message by its CID, e.g. from on_found_new_message_cid.  For a secp message this
+    // is the unsigned message inside it.
+    // Equivalent to `lotus chain getmessage bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.ChainGetMessage",
+    //      "params":[{"/":"bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i"}],
+    //      "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    //
+    // which produces this json:
+    //
+    //   {
+    //     "jsonrpc": "2.0",
+    //     "result": {
+    //       "Version": 0,
+    //       "To": "t01000",
+    //       "From": "t3wowfuawcs6dwddo75jum2ddccrrq376oildvblukm4ctybuo5wtylvht5rahhtsoohoca3vdmyyt2zjlwqoa",
+    //       "Nonce": 20,
+    //       "Value": "0",
+    //       "GasPrice": "0",
+    //       "GasLimit": 10000000,
+    //       "Method": 2,
+    //       "Params": "hVgxA4mX..."
+    //     },
+    //     "id": 0
+    //   }
+    pub async fn chain_get_message(&self, msg_cid: &Cid) -> Result<jsonrpsee::common::JsonValue, LotusError> {
+        make_api_function!(self, "Filecoin.ChainGetMessage",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(msg_cid));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // chain_notify
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // Subscribes to head changes over a websocket to the same endpoint (ws:// instead of
+    // http://).  The stream yields each notification's list of changes, e.g.:
+    //
+    //      [ { "Type": "current", "Val": <tipset> } ]                         first, the head
+    //      [ { "Type": "apply", "Val": <tipset> } ]                           a new tipset
+    //      [ { "Type": "revert", "Val": <tipset> },                          a reorg
+    //        { "Type": "apply", "Val": <tipset> } ]
+    //
+    // and ends when the node closes the channel or the connection drops.  Dropping the
+    // stream unsubscribes.
+    pub async fn chain_notify(&self) -> Result<ws::Subscription<Vec<types::HeadChange>>, LotusError> {
+        self.ws_client().await?.subscribe("Filecoin.ChainNotify", vec!()).await
+    }
+
+    async fn ws_client(&self) -> Result<ws::WsClient, LotusError> {
+        let mut ws_client_guard = self.ws_client.lock().await;
+        if let Some(ws_client) = ws_client_guard.as_ref() {
+            if !ws_client.is_closed() {
+                return Ok(ws_client.clone());
+            }
+        }
+        let ws_url = ws::endpoint_url_to_ws_url(&self.endpoint_url);
+        let ws_client = ws::WsClient::connect(&ws_url, self.auth_token_str(), self.timeout).await?;
+        *ws_client_guard = Some(ws_client.clone());
+        Ok(ws_client)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // Typed variants of the chain methods above
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // Same calls as above, but deserialized into the structs in crate::types instead of
+    // being returned as raw json.
+
+    pub async fn chain_get_tipset_by_height_typed(&self, height: u64) -> Result<types::TipSet, LotusError> {
+        from_json(self.chain_get_tipset_by_height(height).await?)
+    }
+
+    pub async fn chain_get_tipset_typed(&self, tipset_key: &types::TipSetKey) -> Result<types::TipSet, LotusError> {
+        from_json(self.chain_get_tipset(tipset_key).await?)
+    }
+
+    pub async fn chain_head_typed(&self) -> Result<types::TipSet, LotusError> {
+        from_json(self.chain_head().await?)
+    }
+
+    pub async fn chain_get_block_typed(&self, block_cid: &str) -> Result<types::BlockHeader, LotusError> {
+        from_json(self.chain_get_block(block_cid).await?)
+    }
+
+    pub async fn chain_get_block_messages_typed(&self, block_cid: &str) -> Result<types::BlockMessages, LotusError> {
+        from_json(self.chain_get_block_messages(block_cid).await?)
+    }
+
+    pub async fn chain_get_parent_messages_typed(&self, block_cid: &str) -> Result<Vec<types::ParentMessage>, LotusError> {
+        from_json_or_default(self.chain_get_parent_messages(block_cid).await?)
+    }
+
+    pub async fn chain_get_parent_receipts_typed(&self, block_cid: &str) -> Result<Vec<types::MessageReceipt>, LotusError> {
+        from_json_or_default(self.chain_get_parent_receipts(block_cid).await?)
+    }
+
+    pub async fn chain_get_message_typed(&self, msg_cid: &Cid) -> Result<types::UnsignedMessage, LotusError> {
+        from_json(self.chain_get_message(msg_cid).await?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // State queries
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // These are typed only.  Each takes the tipset to evaluate at (e.g. the one a walk has
+    // reached, from tipset.key()); None means the head.
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_get_actor
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // Equivalent to `lotus state get-actor f01000`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateGetActor",
+    //      "params":["f01000",[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    //
+    // which produces this json:
+    //
+    //   {
+    //     "jsonrpc": "2.0",
+    //     "result": {
+    //       "Code": { "/": "bafkqaetgnfwc6mrpon2g64tbm5sw22lomvza" },
+    //       "Head": { "/": "bafy2bzacebazmwzz7v5svaqwxhuk26jtco526k2vlzb5dt4ii6cuoszmq5hx4" },
+    //       "Nonce": 0,
+    //       "Balance": "2500000000000000000"
+    //     },
+    //     "id": 0
+    //   }
+    //
+    // and an rpc error if there is no such actor at that tipset.
+    pub async fn state_get_actor(&self, address: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<types::Actor, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.StateGetActor",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(address));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_lookup_id
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // The ID address (f0...) of an actor known by its robust address (f1, f2, f3, f4), e.g.
+    // to match a message's `to` against an ID from a deal.  An ID address is returned as is.
+    // Equivalent to `lotus state lookup f3...`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateLookupID",
+    //      "params":["t3wzcpwznw6dvl6x3beekspluvhdwh26h3tvmw5y2fychse7pr6xlsfmxuhsv6ki7r3pm6s7gxc65h52lgqfsa",[]],
+    //      "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning e.g. `"t01002"`.
+    pub async fn state_lookup_id(&self, address: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<Address, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.StateLookupID",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(address));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_account_key
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // The reverse of state_lookup_id for account actors:  the public key address (f1 or f3)
+    // of the account at an ID address.
+    // Equivalent to `lotus state lookup --reverse f01002`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateAccountKey",
+    //      "params":["t01002",[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    pub async fn state_account_key(&self, address: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<Address, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.StateAccountKey",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(address));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_list_actors
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // The ID addresses of every actor in the state tree.  On mainnet that is millions of
+    // addresses, so expect it to be slow; raise the client's timeout() first.
+    // Equivalent to `lotus state list-actors`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateListActors",
+    //      "params":[[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    pub async fn state_list_actors(&self, tipset_key: Option<&types::TipSetKey>) -> Result<Vec<Address>, LotusError> {
+        from_json_or_default(make_api_function!(self, "Filecoin.StateListActors",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // wallet_balance
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // The balance of any address (not only the node's own wallets).
+    // Equivalent to `lotus wallet balance f3...`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.WalletBalance",
+    //      "params":["t01002"], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning e.g. `"2500000000000000000"`.
+    //
+    // Filecoin.WalletBalance only looks at the head, and is zero for an address with no
+    // actor yet.  At any other tipset the balance is read from Filecoin.StateGetActor
+    // instead, which fails for an address with no actor at that tipset.
+    pub async fn wallet_balance(&self, address: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<TokenAmount, LotusError> {
+        if tipset_key.is_some() {
+            return Ok(self.state_get_actor(address, tipset_key).await?.balance);
+        }
+        from_json(make_api_function!(self, "Filecoin.WalletBalance",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(address));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_network_name / state_network_version
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // The name of the network the node is on, e.g. "mainnet" or "calibrationnet".  It is
+    // fixed at genesis, so unlike the other state queries this one takes no tipset.
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateNetworkName",
+    //      "params":[], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    pub async fn state_network_name(&self) -> Result<String, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.StateNetworkName",{
+            jsonrpsee::common::Params::Array(vec!())
+        })?)
+    }
+
+    // The network version (which selects the actors version and the rules in force) at a
+    // tipset.  It goes up at each network upgrade, so a walk crossing an upgrade sees it
+    // change.
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateNetworkVersion",
+    //      "params":[[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning e.g. `21`.
+    pub async fn state_network_version(&self, tipset_key: Option<&types::TipSetKey>) -> Result<u64, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.StateNetworkVersion",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_market_deals
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // Every deal the storage market actor knows of, by deal ID.  Like state_list_actors,
+    // this is a very large response on mainnet.
+    // Equivalent to `lotus state list-deals` (without the filtering)
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateMarketDeals",
+    //      "params":[[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    //
+    // which produces this json:
+    //
+    //   {
+    //     "jsonrpc": "2.0",
+    //     "result": {
+    //       "1033": {
+    //         "Proposal": {
+    //           "PieceCID": { "/": "baga6ea4seaqhsxatjuqqcohz4akb6bd2fyhd3ujqu6oe5ywhjcddwjuu3fy6awa" },
+    //           "PieceSize": 34359738368,
+    //           "VerifiedDeal": true,
+    //           "Client": "f01024",
+    //           "Provider": "f01000",
+    //           "Label": "mAXCg5AIg",
+    //           "StartEpoch": 16182,
+    //           "EndEpoch": 49956,
+    //           "StoragePricePerEpoch": "500000000",
+    //           "ProviderCollateral": "133169152",
+    //           "ClientCollateral": "0"
+    //         },
+    //         "State": { "SectorStartEpoch": 16020, "LastUpdatedEpoch": -1, "SlashEpoch": -1 }
+    //       },
+    //       [...deleted (more deals)...]
+    //     },
+    //     "id": 0
+    //   }
+    pub async fn state_market_deals(&self, tipset_key: Option<&types::TipSetKey>) -> Result<std::collections::BTreeMap<u64, types::MarketDeal>, LotusError> {
+        from_json_or_default(make_api_function!(self, "Filecoin.StateMarketDeals",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_market_storage_deal
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // One deal by its ID, e.g. from the return value of a PublishStorageDeals message.
+    // Equivalent to `lotus state get-deal 1033`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateMarketStorageDeal",
+    //      "params":[1033,[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning one deal in the same form as state_market_deals.
+    pub async fn state_market_storage_deal(&self, deal_id: u64, tipset_key: Option<&types::TipSetKey>) -> Result<types::MarketDeal, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.StateMarketStorageDeal",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(deal_id));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_market_balance
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // A client's or provider's escrow in the storage market actor.
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateMarketBalance",
+    //      "params":["f01000",[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning e.g. `{ "Escrow": "2000000000000000000", "Locked": "133169152" }`.
+    pub async fn state_market_balance(&self, address: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<types::MarketBalance, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.StateMarketBalance",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(address));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_market_participants
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // The escrow of every address with funds in the storage market actor.
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateMarketParticipants",
+    //      "params":[[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning e.g. `{ "f01000": { "Escrow": "2000000000000000000", "Locked": "133169152" }, ... }`.
+    pub async fn state_market_participants(&self, tipset_key: Option<&types::TipSetKey>) -> Result<std::collections::HashMap<Address, types::MarketBalance>, LotusError> {
+        from_json_or_default(make_api_function!(self, "Filecoin.StateMarketParticipants",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_list_miners
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // The ID addresses of every miner actor, whether or not it has any power.
+    // Equivalent to `lotus state list-miners`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateListMiners",
+    //      "params":[[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    pub async fn state_list_miners(&self, tipset_key: Option<&types::TipSetKey>) -> Result<Vec<Address>, LotusError> {
+        from_json_or_default(make_api_function!(self, "Filecoin.StateListMiners",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_miner_info
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // A miner's owner, worker and control addresses, peer id, multiaddrs and sector size.
+    // Equivalent to `lotus state miner-info f01000`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateMinerInfo",
+    //      "params":["f01000",[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    //
+    // which produces this json:
+    //
+    //   {
+    //     "jsonrpc": "2.0",
+    //     "result": {
+    //       "Owner": "f01001",
+    //       "Worker": "f01002",
+    //       "NewWorker": "<empty>",
+    //       "ControlAddresses": null,
+    //       "WorkerChangeEpoch": -1,
+    //       "PeerId": "12D3KooWPEMEb8bn4KbUFfVpbnMdTtC3vHaWsnaNKTtNZQcNSsHB",
+    //       "Multiaddrs": [ "BMCoAXUGXcE=" ],
+    //       "WindowPoStProofType": 8,
+    //       "SectorSize": 34359738368,
+    //       "WindowPoStPartitionSectors": 2349,
+    //       "ConsensusFaultElapsed": -1
+    //     },
+    //     "id": 0
+    //   }
+    pub async fn state_miner_info(&self, miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<types::MinerInfo, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.StateMinerInfo",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(miner));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_miner_power
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // A miner's power, and the whole network's.
+    // Equivalent to `lotus state power f01000`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateMinerPower",
+    //      "params":["f01000",[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning e.g.
+    //
+    //   {
+    //     "MinerPower": { "RawBytePower": "2336462209024", "QualityAdjPower": "2336462209024" },
+    //     "TotalPower": { "RawBytePower": "9851570995281920", "QualityAdjPower": "23048493023363072" },
+    //     "HasMinPower": true
+    //   }
+    pub async fn state_miner_power(&self, miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<types::MinerPower, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.StateMinerPower",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(miner));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_miner_sectors
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // A miner's sectors, including faulty and expired ones not yet cleaned up; only those in
+    // `sectors` if given.
+    // Equivalent to `lotus state sectors f01000`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateMinerSectors",
+    //      "params":["f01000",null,[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning a list of sectors like
+    //
+    //   {
+    //     "SectorNumber": 3,
+    //     "SealProof": 8,
+    //     "SealedCID": { "/": "bagboea4b5abcblraaktvfcyfrx4ugzc4t2bp3qftvhs35fkac3xmtbhal42a3wmd" },
+    //     "DealIDs": [ 1033 ],
+    //     "Activation": 16020,
+    //     "Expiration": 1570020,
+    //     "DealWeight": "1160505346572288",
+    //     "VerifiedDealWeight": "0",
+    //     "InitialPledge": "158405640277856768",
+    //     "ExpectedDayReward": "1158018735651962",
+    //     "ExpectedStoragePledge": "22998932745466708"
+    //   }
+    pub async fn state_miner_sectors(&self, miner: &Address, sectors: Option<&types::BitField>, tipset_key: Option<&types::TipSetKey>) -> Result<Vec<types::SectorOnChainInfo>, LotusError> {
+        from_json_or_default(make_api_function!(self, "Filecoin.StateMinerSectors",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(miner));
+            v_params.push(json!(sectors));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_miner_active_sectors
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // The sectors a miner is proving:  not faulty, terminated or expired.
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateMinerActiveSectors",
+    //      "params":["f01000",[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning a list of sectors in the same form as state_miner_sectors.
+    pub async fn state_miner_active_sectors(&self, miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<Vec<types::SectorOnChainInfo>, LotusError> {
+        from_json_or_default(make_api_function!(self, "Filecoin.StateMinerActiveSectors",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(miner));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_miner_faults
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // The numbers of a miner's faulty sectors.
+    // Equivalent to `lotus-miner proving faults`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateMinerFaults",
+    //      "params":["f01000",[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning a bitfield, e.g. `[3, 2]` for sectors 3 and 4.
+    pub async fn state_miner_faults(&self, miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<types::BitField, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.StateMinerFaults",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(miner));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_miner_recoveries
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // The numbers of a miner's faulty sectors it has declared recovered, which will count
+    // again once proven at their next deadline.
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateMinerRecoveries",
+    //      "params":["f01000",[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning a bitfield like state_miner_faults.
+    pub async fn state_miner_recoveries(&self, miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<types::BitField, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.StateMinerRecoveries",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(miner));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_miner_deadlines
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // The state of each of a miner's window post deadlines, in order (48 of them).
+    // Equivalent to `lotus-miner proving deadlines`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateMinerDeadlines",
+    //      "params":["f01000",[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning e.g. `[ { "PostSubmissions": [0, 1], "DisputableProofCount": 0 }, ... ]`.
+    pub async fn state_miner_deadlines(&self, miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<Vec<types::Deadline>, LotusError> {
+        from_json_or_default(make_api_function!(self, "Filecoin.StateMinerDeadlines",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(miner));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_miner_proving_deadline
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // Where a miner is in its proving period:  which deadline is open, and its epochs.
+    // Equivalent to `lotus-miner proving info`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateMinerProvingDeadline",
+    //      "params":["f01000",[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    //
+    // which produces this json:
+    //
+    //   {
+    //     "jsonrpc": "2.0",
+    //     "result": {
+    //       "CurrentEpoch": 1035512,
+    //       "PeriodStart": 1034521,
+    //       "Index": 16,
+    //       "Open": 1035481,
+    //       "Close": 1035541,
+    //       "Challenge": 1035461,
+    //       "FaultCutoff": 1035411,
+    //       "WPoStPeriodDeadlines": 48,
+    //       "WPoStProvingPeriod": 2880,
+    //       "WPoStChallengeWindow": 60,
+    //       "WPoStChallengeLookback": 20,
+    //       "FaultDeclarationCutoff": 70
+    //     },
+    //     "id": 0
+    //   }
+    pub async fn state_miner_proving_deadline(&self, miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<types::DeadlineInfo, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.StateMinerProvingDeadline",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(miner));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_miner_available_balance
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // What a miner could withdraw:  its balance less locked rewards, pre-commit deposits,
+    // initial pledge and fee debt.
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateMinerAvailableBalance",
+    //      "params":["f01000",[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning e.g. `"1203049596021540981"`.
+    pub async fn state_miner_available_balance(&self, miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<TokenAmount, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.StateMinerAvailableBalance",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(miner));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_sector_get_info
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // One of a miner's sectors, or None if the miner has no such sector.
+    // Equivalent to `lotus state sector f01000 3`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateSectorGetInfo",
+    //      "params":["f01000",3,[]], "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    // returning one sector in the same form as state_miner_sectors, or null.
+    pub async fn state_sector_get_info(&self, miner: &Address, sector_number: u64, tipset_key: Option<&types::TipSetKey>) -> Result<Option<types::SectorOnChainInfo>, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.StateSectorGetInfo",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(miner));
+            v_params.push(json!(sector_number));
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_search_msg
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // Where a message was executed, searching back from the head:  its receipt, and the
+    // tipset whose parent state includes its execution.  None if it hasn't been executed
+    // yet, or not within `lookback_limit` epochs of the head if given.  The result's
+    // `message` is the CID of the message executed (see types::MsgLookup), not the message;
+    // chain_get_message_typed() fetches that.
+    // Equivalent to `lotus state search-msg bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateSearchMsgLimited",
+    //      "params":[{"/":"bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i"},2880],
+    //      "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    //
+    // which produces this json:
+    //
+    //   {
+    //     "jsonrpc": "2.0",
+    //     "result": {
+    //       "Message": { "/": "bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i" },
+    //       "Receipt": { "ExitCode": 0, "Return": null, "GasUsed": 1694476 },
+    //       "ReturnDec": null,
+    //       "TipSet": [ { "/": "bafy2bzacebazmwzz7v5svaqwxhuk26jtco526k2vlzb5dt4ii6cuoszmq5hx4" } ],
+    //       "Height": 97222
+    //     },
+    //     "id": 0
+    //   }
+    //
+    // (Filecoin.StateSearchMsg, without the limit, when lookback_limit is None.)
+    pub async fn state_search_msg(&self, msg_cid: &Cid, lookback_limit: Option<u64>) -> Result<Option<types::MsgLookup>, LotusError> {
+        from_json(match lookback_limit {
+            Some(lookback_limit) => make_api_function!(self, "Filecoin.StateSearchMsgLimited",{
+                let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+                v_params.push(json!(msg_cid));
+                v_params.push(json!(lookback_limit));
+                let params = jsonrpsee::common::Params::Array(v_params);
+                params
+            })?,
+            None => make_api_function!(self, "Filecoin.StateSearchMsg",{
+                let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+                v_params.push(json!(msg_cid));
+                let params = jsonrpsee::common::Params::Array(v_params);
+                params
+            })?,
+        })
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // state_wait_msg
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // Waits for a message to be executed and then for `confidence` more epochs on top of
+    // the tipset executing it, and returns where it was executed (as state_search_msg, so
+    // again with only the message's CID).
+    // The node searches back (no further than `lookback_limit` epochs, if given) before it
+    // starts waiting.
+    //
+    // The wait is made on a connection of its own, and gives up with LotusError::Timeout after
+    // `timeout` rather than the client's usual timeout.
+    // Equivalent to `lotus state wait-msg --confidence 5 bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.StateWaitMsgLimited",
+    //      "params":[{"/":"bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i"},5,2880],
+    //      "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    pub async fn state_wait_msg(&self, msg_cid: &Cid, confidence: u64, lookback_limit: Option<u64>,
+        timeout: std::time::Duration) -> Result<types::MsgLookup, LotusError>
+    {
+        let mut waiter = self.new_connection();
+        waiter.timeout(timeout);
+        from_json(match lookback_limit {
+            Some(lookback_limit) => make_api_function!(waiter, "Filecoin.StateWaitMsgLimited",{
+                let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+                v_params.push(json!(msg_cid));
+                v_params.push(json!(confidence));
+                v_params.push(json!(lookback_limit));
+                let params = jsonrpsee::common::Params::Array(v_params);
+                params
+            })?,
+            None => make_api_function!(waiter, "Filecoin.StateWaitMsg",{
+                let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+                v_params.push(json!(msg_cid));
+                v_params.push(json!(confidence));
+                let params = jsonrpsee::common::Params::Array(v_params);
+                params
+            })?,
+        })
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // mpool_pending
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // The messages in the node's pool that haven't been included in `tipset_key` (None means
+    // the head) or before it.  BLS messages come back signed too, with their own signature
+    // rather than a block's aggregate.  blockanalyzer::Message::from_signed_message() turns
+    // each into the same Message the chain walk reports, with the CID it reports it by.
+    // Equivalent to `lotus mpool pending`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.MpoolPending", "params":[[]], "id": 0 }'
+    //      'http://lotus1:1234/rpc/v0'
+    //
+    // which produces this json:
+    //
+    //   {
+    //     "jsonrpc": "2.0",
+    //     "result": [
+    //       {
+    //         "Message": {
+    //           "Version": 0,
+    //           "To": "f01000",
+    //           "From": "f1abjxfbp274xpdqcpuaykwkfb43omjotacm2p3za",
+    //           "Nonce": 7,
+    //           "Value": "0",
+    //           "GasLimit": 24000000,
+    //           "GasFeeCap": "101310",
+    //           "GasPremium": "100256",
+    //           "Method": 5,
+    //           "Params": "hwGBggBAgYIAQA=="
+    //         },
+    //         "Signature": { "Type": 1, "Data": "8hHyT3DRbmC+M9VvzFHQ..." },
+    //         "CID": { "/": "bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i" }
+    //       }
+    //     ],
+    //     "id": 0
+    //   }
+    pub async fn mpool_pending(&self, tipset_key: Option<&types::TipSetKey>) -> Result<Vec<types::SignedMessage>, LotusError> {
+        from_json_or_default(make_api_function!(self, "Filecoin.MpoolPending",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(tipset_key_param(tipset_key));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // mpool_select
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // The pending messages the node would put in a block on top of `tipset_key`, best first.
+    // `ticket_quality` (0.0 to 1.0) is how likely the block is to be the first in its
+    // tipset, which decides how much the node counts on the fees of messages other miners
+    // may take first.
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.MpoolSelect", "params":[[],0.9], "id": 0 }'
+    //      'http://lotus1:1234/rpc/v0'
+    pub async fn mpool_select(&self, tipset_key: Option<&types::TipSetKey>, ticket_quality: f64) -> Result<Vec<types::SignedMessage>, LotusError> {
+        from_json_or_default(make_api_function!(self, "Filecoin.MpoolSelect",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(tipset_key_param(tipset_key));
+            v_params.push(json!(ticket_quality));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // mpool_get_nonce
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // The nonce for the next message from `address`, counting its messages still in the pool
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.MpoolGetNonce",
+    //      "params":["f1abjxfbp274xpdqcpuaykwkfb43omjotacm2p3za"], "id": 0 }'
+    //      'http://lotus1:1234/rpc/v0'
+    //
+    // which produces this json:
+    //
+    //   { "jsonrpc": "2.0", "result": 8, "id": 0 }
+    pub async fn mpool_get_nonce(&self, address: &Address) -> Result<u64, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.MpoolGetNonce",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(address));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // mpool_push
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // Adds a message signed elsewhere to the pool (and gossips it), returning its CID.
+    // Needs a token with write permission.
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" -H "Authorization: Bearer $TOKEN" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.MpoolPush",
+    //      "params":[{"Message":{...},"Signature":{"Type":1,"Data":"..."}}], "id": 0 }'
+    //      'http://lotus1:1234/rpc/v0'
+    //
+    // which produces this json:
+    //
+    //   {
+    //     "jsonrpc": "2.0",
+    //     "result": { "/": "bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i" },
+    //     "id": 0
+    //   }
+    pub async fn mpool_push(&self, signed_message: &types::SignedMessage) -> Result<Cid, LotusError> {
+        from_json(make_api_function!(self, "Filecoin.MpoolPush",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(signed_message));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // mpool_push_message
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // Has the node fill in the nonce and any gas fields left at zero, sign the message with
+    // the From key in its wallet, and push it; returns the message as signed.  Needs a token
+    // with sign permission.  `spec` None means the node's default fee limit.
+    // Equivalent to `lotus send`
+    // Equivalent curl:  curl -X POST -H "Content-Type: application/json" -H "Authorization: Bearer $TOKEN" --data
+    //      '{ "jsonrpc": "2.0", "method": "Filecoin.MpoolPushMessage",
+    //      "params":[{"To":"f01000","From":"f1abjxfbp274xpdqcpuaykwkfb43omjotacm2p3za","Value":"1000",...},null],
+    //      "id": 0 }' 'http://lotus1:1234/rpc/v0'
+    pub async fn mpool_push_message(&self, message: &types::UnsignedMessage, spec: Option<&types::MessageSendSpec>)
+        -> Result<types::SignedMessage, LotusError>
+    {
+        from_json(make_api_function!(self, "Filecoin.MpoolPushMessage",{
+            let mut v_params : Vec<jsonrpsee::common::JsonValue> = vec!();
+            v_params.push(json!(message));
+            v_params.push(json!(spec));
+            let params = jsonrpsee::common::Params::Array(v_params);
+            params
+        })?)
+    }
+
+    //////////////////////////////////////////////////////////////////////////////////////
+    //
+    // mpool_sub
+    //
+    //////////////////////////////////////////////////////////////////////////////////////
+
+    // Subscribes to changes to the node's pool over a websocket, as chain_notify does to
+    // head changes.  Each message added to or removed from the pool is one item of the
+    // stream:
+    //
+    //      { "Type": 0, "Message": <signed message> }          added
+    //      { "Type": 1, "Message": <signed message> }          removed
+    //
+    // Dropping the stream unsubscribes.
+    pub async fn mpool_sub(&self) -> Result<ws::Subscription<types::MpoolUpdate>, LotusError> {
+        self.ws_client().await?.subscribe("Filecoin.MpoolSub", vec!()).await
+    }
+}
+
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// ApiClient - blocking wrapper around AsyncApiClient for scripts and the block walker
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+macro_rules! make_blocking_api_functions {
+    ( $( fn $fn_name:ident ( $( $arg:ident : $arg_type:ty ),* ) -> $ret_type:ty ; )* ) => {
+        $(
+            pub fn $fn_name(&self, $( $arg: $arg_type ),* ) -> $ret_type {
+                async_std::task::block_on(self.inner.$fn_name( $( $arg ),* ))
+            }
+        )*
+    }
+}
+
+#[derive(Clone)]
+pub struct ApiClient {
+    inner: AsyncApiClient,
+}
+
+impl ApiClient {
+    pub fn new(endpoint_url: &str) -> ApiClient {
+        ApiClient{
+            inner : AsyncApiClient::new(endpoint_url),
+        }
+    }
+
+    pub fn from_api_info(api_info: &str) -> Result<ApiClient, LotusError> {
+        Ok(ApiClient{ inner: AsyncApiClient::from_api_info(api_info)? })
+    }
+
+    pub fn from_env() -> Result<ApiClient, LotusError> {
+        Ok(ApiClient{ inner: AsyncApiClient::from_env()? })
+    }
+
+    pub fn from_repo_path<P: AsRef<std::path::Path>>(repo_path: P) -> Result<ApiClient, LotusError> {
+        Ok(ApiClient{ inner: AsyncApiClient::from_repo_path(repo_path)? })
+    }
+
+    pub fn auth_token(& mut self, auth_token: &str) {
+        self.inner.auth_token(auth_token);
+    }
+
+    pub fn endpoint(& mut self, endpoint: &str) {
+        self.inner.endpoint(endpoint);
+    }
+
+    pub fn timeout(& mut self, timeout: std::time::Duration) {
+        self.inner.timeout(timeout);
+    }
+
+    pub fn new_connection(&self) -> ApiClient {
+        ApiClient{ inner: self.inner.new_connection() }
+    }
+
+    // The async client underneath; it shares this client's transports.
+    pub fn as_async(&self) -> &AsyncApiClient {
+        &self.inner
+    }
+
+    make_blocking_api_functions! {
+        fn check_endpoint_connection() -> Result<(), LotusError>;
+        fn chain_get_tipset_by_height(height: u64) -> Result<jsonrpsee::common::JsonValue, LotusError>;
+        fn chain_get_tipset(tipset_key: &types::TipSetKey) -> Result<jsonrpsee::common::JsonValue, LotusError>;
+        fn chain_head() -> Result<jsonrpsee::common::JsonValue, LotusError>;
+        fn chain_get_block(block_cid: &str) -> Result<jsonrpsee::common::JsonValue, LotusError>;
+        fn chain_get_block_messages(block_cid: &str) -> Result<jsonrpsee::common::JsonValue, LotusError>;
+        fn chain_get_parent_messages(block_cid: &str) -> Result<jsonrpsee::common::JsonValue, LotusError>;
+        fn chain_get_parent_receipts(block_cid: &str) -> Result<jsonrpsee::common::JsonValue, LotusError>;
+        fn chain_get_tipset_by_height_typed(height: u64) -> Result<types::TipSet, LotusError>;
+        fn chain_get_tipset_typed(tipset_key: &types::TipSetKey) -> Result<types::TipSet, LotusError>;
+        fn chain_head_typed() -> Result<types::TipSet, LotusError>;
+        fn chain_get_block_typed(block_cid: &str) -> Result<types::BlockHeader, LotusError>;
+        fn chain_get_block_messages_typed(block_cid: &str) -> Result<types::BlockMessages, LotusError>;
+        fn chain_get_parent_messages_typed(block_cid: &str) -> Result<Vec<types::ParentMessage>, LotusError>;
+        fn chain_get_parent_receipts_typed(block_cid: &str) -> Result<Vec<types::MessageReceipt>, LotusError>;
+        fn chain_get_message(msg_cid: &Cid) -> Result<jsonrpsee::common::JsonValue, LotusError>;
+        fn chain_get_message_typed(msg_cid: &Cid) -> Result<types::UnsignedMessage, LotusError>;
+        fn state_get_actor(address: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<types::Actor, LotusError>;
+        fn state_lookup_id(address: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<Address, LotusError>;
+        fn state_account_key(address: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<Address, LotusError>;
+        fn state_list_actors(tipset_key: Option<&types::TipSetKey>) -> Result<Vec<Address>, LotusError>;
+        fn wallet_balance(address: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<TokenAmount, LotusError>;
+        fn state_network_name() -> Result<String, LotusError>;
+        fn state_network_version(tipset_key: Option<&types::TipSetKey>) -> Result<u64, LotusError>;
+        fn state_market_deals(tipset_key: Option<&types::TipSetKey>) -> Result<std::collections::BTreeMap<u64, types::MarketDeal>, LotusError>;
+        fn state_market_storage_deal(deal_id: u64, tipset_key: Option<&types::TipSetKey>) -> Result<types::MarketDeal, LotusError>;
+        fn state_market_balance(address: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<types::MarketBalance, LotusError>;
+        fn state_market_participants(tipset_key: Option<&types::TipSetKey>) -> Result<std::collections::HashMap<Address, types::MarketBalance>, LotusError>;
+        fn state_list_miners(tipset_key: Option<&types::TipSetKey>) -> Result<Vec<Address>, LotusError>;
+        fn state_miner_info(miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<types::MinerInfo, LotusError>;
+        fn state_miner_power(miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<types::MinerPower, LotusError>;
+        fn state_miner_sectors(miner: &Address, sectors: Option<&types::BitField>, tipset_key: Option<&types::TipSetKey>) -> Result<Vec<types::SectorOnChainInfo>, LotusError>;
+        fn state_miner_active_sectors(miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<Vec<types::SectorOnChainInfo>, LotusError>;
+        fn state_miner_faults(miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<types::BitField, LotusError>;
+        fn state_miner_recoveries(miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<types::BitField, LotusError>;
+        fn state_miner_deadlines(miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<Vec<types::Deadline>, LotusError>;
+        fn state_miner_proving_deadline(miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<types::DeadlineInfo, LotusError>;
+        fn state_miner_available_balance(miner: &Address, tipset_key: Option<&types::TipSetKey>) -> Result<TokenAmount, LotusError>;
+        fn state_search_msg(msg_cid: &Cid, lookback_limit: Option<u64>) -> Result<Option<types::MsgLookup>, LotusError>;
+        fn state_wait_msg(msg_cid: &Cid, confidence: u64, lookback_limit: Option<u64>, timeout: std::time::Duration) -> Result<types::MsgLookup, LotusError>;
+        fn state_sector_get_info(miner: &Address, sector_number: u64, tipset_key: Option<&types::TipSetKey>) -> Result<Option<types::SectorOnChainInfo>, LotusError>;
+        fn mpool_pending(tipset_key: Option<&types::TipSetKey>) -> Result<Vec<types::SignedMessage>, LotusError>;
+        fn mpool_select(tipset_key: Option<&types::TipSetKey>, ticket_quality: f64) -> Result<Vec<types::SignedMessage>, LotusError>;
+        fn mpool_get_nonce(address: &Address) -> Result<u64, LotusError>;
+        fn mpool_push(signed_message: &types::SignedMessage) -> Result<Cid, LotusError>;
+        fn mpool_push_message(message: &types::UnsignedMessage, spec: Option<&types::MessageSendSpec>) -> Result<types::SignedMessage, LotusError>;
+    }
+}
+
+impl From<AsyncApiClient> for ApiClient {
+    fn from(inner: AsyncApiClient) -> ApiClient {
+        ApiClient{ inner: inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multiaddr_to_endpoint_url() {
+        assert_eq!(multiaddr_to_endpoint_url("/ip4/10.0.0.5/tcp/1234/http").unwrap(), "http://10.0.0.5:1234/rpc/v0");
+        assert_eq!(multiaddr_to_endpoint_url("/ip4/0.0.0.0/tcp/1234/http").unwrap(), "http://127.0.0.1:1234/rpc/v0");
+        assert_eq!(multiaddr_to_endpoint_url("/dns/lotus1/tcp/2345/https").unwrap(), "https://lotus1:2345/rpc/v0");
+        assert_eq!(multiaddr_to_endpoint_url("/ip6/::1/tcp/1234/http").unwrap(), "http://[::1]:1234/rpc/v0");
+        assert_eq!(multiaddr_to_endpoint_url("/ip4/10.0.0.5/tcp/1234/ws").unwrap(), "http://10.0.0.5:1234/rpc/v0");
+        assert_eq!(multiaddr_to_endpoint_url("/dns/lotus1/tcp/2345/wss").unwrap(), "https://lotus1:2345/rpc/v0");
+        assert!(multiaddr_to_endpoint_url("/ip4/10.0.0.5/http").is_err());
+        assert!(multiaddr_to_endpoint_url("/unix/tmp/lotus.sock").is_err());
+    }
+
+    #[test]
+    fn test_tipset_key_param() {
+        assert_eq!(tipset_key_param(None), json!([]));
+        let tipset_key = types::TipSetKey(vec!["bafy2bzacebazmwzz7v5svaqwxhuk26jtco526k2vlzb5dt4ii6cuoszmq5hx4".parse().unwrap()]);
+        assert_eq!(tipset_key_param(Some(&tipset_key)), json!([{"/": "bafy2bzacebazmwzz7v5svaqwxhuk26jtco526k2vlzb5dt4ii6cuoszmq5hx4"}]));
+    }
+
+    #[test]
+    fn test_from_api_info() {
+        let api = AsyncApiClient::from_api_info("abc.def.ghi:/ip4/10.0.0.5/tcp/1234/http").unwrap();
+        assert_eq!(api.endpoint_url, "http://10.0.0.5:1234/rpc/v0");
+        assert_eq!(api.auth_token_str(), "abc.def.ghi");
+
+        let api = AsyncApiClient::from_api_info("/ip6/::1/tcp/1234/http").unwrap();
+        assert_eq!(api.endpoint_url, "http://[::1]:1234/rpc/v0");
+        assert_eq!(api.auth_token_str(), "");
+
+        assert!(AsyncApiClient::from_api_info("just-a-token").is_err());
+    }
+
+    #[test]
+    fn test_from_repo_path() {
+        let repo_path = std::env::temp_dir().join(format!("lotus_client_rs_test_repo_{}",std::process::id()));
+        std::fs::create_dir_all(&repo_path).unwrap();
+        std::fs::write(repo_path.join("token"), "abc.def.ghi\n").unwrap();
+        std::fs::write(repo_path.join("config.toml"), "[API]\n  ListenAddress = \"/ip4/0.0.0.0/tcp/2345/http\"\n").unwrap();
+        let api = AsyncApiClient::from_repo_path(&repo_path).unwrap();
+        assert_eq!(api.endpoint_url, "http://127.0.0.1:2345/rpc/v0");
+        assert_eq!(api.auth_token_str(), "abc.def.ghi");
+
+        std::fs::write(repo_path.join("config.toml"), "[API]\n#  ListenAddress = \"/ip4/0.0.0.0/tcp/2345/http\"\n").unwrap();
+        let api = AsyncApiClient::from_repo_path(&repo_path).unwrap();
+        assert_eq!(api.endpoint_url, "http://127.0.0.1:1234/rpc/v0");
+        std::fs::remove_dir_all(&repo_path).unwrap();
+    }
+}
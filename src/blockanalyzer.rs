@@ -1,9 +1,31 @@
 use log;
 use crate::api;
+use crate::actors::{builtin_method_name, ActorType, MethodRegistry, METHOD_SEND};
+use crate::address::Address;
+use crate::cid::Cid;
+use crate::tokenamount::{TokenAmount, Unit};
+use crate::types::SignedMessage;
+use crate::error::{LotusError, WalkError, WalkErrorKind};
+use crate::visitor::{ChainVisitor, CallbackVisitor};
+use crate::checkpoint::{Checkpoint, CheckpointStore, FileCheckpointStore, SharedCheckpointStore};
+use crate::msgcache::{IncompleteMessage, IncompleteMessageCache, MessageCacheMetrics, DEFAULT_MESSAGE_CACHE_EPOCHS};
 use std::collections::HashMap;
 // See https://github.com/rust-lang/rust/issues/57966 re why this is commented
 //#[macro_use] use crate::macro;
 
+// For the constructors that can't fail (Tipsets::new() etc.):  a failed call is logged and
+// treated as an empty (Null) response, which the json pointer lookups skip over.  The walker
+// uses the try_new() variants instead.
+fn json_or_null(what: &str, result: Result<jsonrpsee::common::JsonValue, LotusError>) -> jsonrpsee::common::JsonValue {
+    match result {
+        Ok(jsonval) => jsonval,
+        Err(e) => {
+            log::error!("{} failed: {}",what,e);
+            jsonrpsee::common::JsonValue::Null
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BlsAggregateSignature {
     type_num: i64,
@@ -35,11 +57,36 @@ impl SecpkSignature {
     }
 }
 
+// A BLS message's own signature, which it only carries until a block aggregates it
+#[derive(Debug, Clone)]
+pub struct BlsSignature {
+    type_num: i64,
+    data: String,
+}
+impl BlsSignature {
+    fn new(type_num: i64, data: &str) -> BlsSignature {
+        BlsSignature{
+            type_num: type_num,
+            data: String::from(data),
+        }
+    }
+    pub fn type_num(&self) -> i64 {
+        self.type_num
+    }
+    // base64 of the signature bytes
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum MessageTypeFlag {
     Unknown,
+    // A BLS message included in a block, with the block's aggregate signature
     BlsMessage(BlsAggregateSignature),
     SecpMessage(SecpkSignature),
+    // A BLS message from the pool (see Message::from_signed_message()), not yet included
+    BlsSignedMessage(BlsSignature),
 }
 
 #[derive(Debug, Clone)]
@@ -55,19 +102,41 @@ pub enum ReceiptStatus {
     #[allow(dead_code)] NoReceipt,
 }
 
+// A tipset as the walker reports it:  its height and the cids of its blocks
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TipsetRef {
+    pub height: u64,
+    pub blocks: Vec<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Message {
     pub msg_type: MessageTypeFlag,
     pub version: u64, 
-    pub to: String,
-    pub from: String,
+    // None if the node sent an address that doesn't parse
+    pub to: Option<Address>,
+    pub from: Option<Address>,
     pub nonce: u64,
-    pub value: String,
-    pub gas_price: String,
+    pub value: TokenAmount,
+    pub gas_fee_cap: TokenAmount,
+    pub gas_premium: TokenAmount,
+    // Only set for messages from before the switch to gas_fee_cap/gas_premium, which
+    // carried a GasPrice instead
+    pub gas_price: Option<TokenAmount>,
     pub gas_limit: u64,
     pub method: String,
+    // The method's name, if known:  Send, and the methods of the singleton actors (the
+    // market at f05 and so on), are named as the message is built; for the rest, call
+    // resolve_method_name() with the recipient's actor code
+    pub method_name: Option<String>,
     pub params: String,
     pub receipt: ReceiptStatus,
+    // The tipset whose blocks included the message, and the first of those blocks seen
+    // to include it (None if that was before the walk started)
+    pub included_in: Option<TipsetRef>,
+    pub included_in_block: Option<String>,
+    // The tipset that executed it, i.e. whose blocks' ParentMessageReceipts hold its receipt
+    pub executed_in: Option<TipsetRef>,
 }
 
 impl Message {
@@ -75,25 +144,126 @@ impl Message {
         Message{
             msg_type: MessageTypeFlag::Unknown,
             version : 0,
-            to : "".to_string(),
-            from : "".to_string(),
+            to : None,
+            from : None,
             nonce : 0,
-            value : "0".to_string(),
-            gas_price : "0".to_string(),
+            value : TokenAmount::zero(),
+            gas_fee_cap : TokenAmount::zero(),
+            gas_premium : TokenAmount::zero(),
+            gas_price : None,
             gas_limit : 0,
             method : "0".to_string(),
+            method_name : None,
             params : "".to_string(),
             receipt: ReceiptStatus::NoReceipt,
+            included_in: None,
+            included_in_block: None,
+            executed_in: None,
         }
     }
+
+    pub fn method_number(&self) -> Option<u64> {
+        self.method.parse().ok()
+    }
+
+    // Names the method from `to_actor_code`, the code CID of the recipient actor (as
+    // Filecoin.StateGetActor reports it)
+    pub fn resolve_method_name(&mut self, registry: &MethodRegistry, to_actor_code: &Cid) -> Option<&str> {
+        if let Some(name) = self.method_number().and_then(|method| registry.method_name_for_code(to_actor_code, method)) {
+            self.method_name = Some(name.to_string());
+        }
+        self.method_name.as_deref()
+    }
+
+    // A message from the pool (Filecoin.MpoolPending, MpoolSub, ...), as the same kind of
+    // Message the chain walk reports, so both can go through one code path.  It has no
+    // receipt or tipsets yet.  Also returns the message's CID, the msg_cid the walk will
+    // report it with, if the node sent one (older nodes don't).
+    pub fn from_signed_message(signed_message: &SignedMessage) -> (Option<Cid>, Message) {
+        let data = &signed_message.signature.data;
+        let msg_type = match signed_message.signature.sig_type {
+            1 => MessageTypeFlag::SecpMessage(SecpkSignature::new(1, data)),
+            2 => MessageTypeFlag::BlsSignedMessage(BlsSignature::new(2, data)),
+            _ => MessageTypeFlag::Unknown,
+        };
+        let unsigned = &signed_message.message;
+        let mut message = Message::new();
+        message.msg_type    = msg_type;
+        message.version     = unsigned.version;
+        message.to          = Some(unsigned.to.clone());
+        message.from        = Some(unsigned.from.clone());
+        message.nonce       = unsigned.nonce;
+        message.value       = unsigned.value.clone();
+        message.gas_fee_cap = unsigned.gas_fee_cap.clone().unwrap_or_else(TokenAmount::zero);
+        message.gas_premium = unsigned.gas_premium.clone().unwrap_or_else(TokenAmount::zero);
+        message.gas_price   = unsigned.gas_price.clone();
+        message.gas_limit   = unsigned.gas_limit;
+        message.method      = unsigned.method.to_string();
+        message.method_name = well_known_method_name(message.to.as_ref(), Some(unsigned.method));
+        message.params      = unsigned.params.clone();
+        (signed_message.cid.clone(), message)
+    }
+}
+
+// Send, or a method of a singleton actor, which need no lookup of the recipient
+fn well_known_method_name(to: Option<&Address>, method: Option<u64>) -> Option<String> {
+    let method = method?;
+    let actor = match method {
+        METHOD_SEND => ActorType::Account,
+        _ => ActorType::from_singleton_address(to?)?,
+    };
+    builtin_method_name(actor, method).map(|name| name.to_string())
 }
 
 impl std::fmt::Display for Message {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "To: {}\nFrom: {}\nType: {:?}\nVersion: {}\nNonce: {}\nValue: {}\nGas price: {}\nGas limit: {}\nMethod: {}\nParams: {}\nReceipt: {:?}\n", 
-                self.to, self.from, self.msg_type, self.version, self.nonce,
-                self.value, self.gas_price, self.gas_limit, self.method, self.params,
-                self.receipt)
+        write!(f, "To: {}\nFrom: {}\nType: {:?}\nVersion: {}\nNonce: {}\nValue: {}\nGas fee cap: {}\nGas premium: {}\n",
+                address_or_empty(&self.to), address_or_empty(&self.from), self.msg_type, self.version, self.nonce,
+                self.value, self.gas_fee_cap.format(Unit::AttoFil), self.gas_premium.format(Unit::AttoFil))?;
+        if let Some(gas_price) = &self.gas_price {
+            write!(f, "Gas price: {}\n", gas_price.format(Unit::AttoFil))?;
+        }
+        write!(f, "Gas limit: {}\nMethod: {}{}\nParams: {}\nReceipt: {:?}\n",
+                self.gas_limit, self.method, self.method_name.as_ref().map(|name| format!(" ({})", name)).unwrap_or_default(), self.params,
+                self.receipt)?;
+        if let Some(included_in) = &self.included_in {
+            match &self.included_in_block {
+                Some(blk_cid) => write!(f, "Included at: {} (block {})\n", included_in.height, blk_cid)?,
+                None => write!(f, "Included at: {}\n", included_in.height)?,
+            }
+        }
+        if let Some(executed_in) = &self.executed_in {
+            write!(f, "Executed at: {}\n", executed_in.height)?;
+        }
+        Ok(())
+    }
+}
+
+fn address_or_empty(address: &Option<Address>) -> String {
+    address.as_ref().map(|address| address.to_string()).unwrap_or_default()
+}
+
+// A message's Value or one of its gas amounts, logging (and zeroing) one that doesn't parse
+fn parse_token_amount(field: &str, amount_str: &str) -> TokenAmount {
+    match amount_str.parse() {
+        Ok(amount) => amount,
+        Err(e) => {
+            log::error!("MessageBuilder: bad {}: {}",field,e);
+            TokenAmount::zero()
+        }
+    }
+}
+
+// A message's To or From, logging (and leaving out) one that doesn't parse
+fn parse_address(field: &str, address_str: &str) -> Option<Address> {
+    match address_str.parse() {
+        Ok(address) => Some(address),
+        Err(e) => {
+            if !address_str.is_empty() {
+                log::error!("MessageBuilder: bad {} address: {}",field,e);
+            }
+            None
+        }
     }
 }
 
@@ -118,17 +288,23 @@ impl MessageBuilder {
         crate::json_val_to_u64!(    "/Nonce",   msg_jsonval, nonce_u64,        0);
         crate::json_val_to_string!( "/Value",   msg_jsonval, value_str,      "0");
         crate::json_val_to_u64!(    "/GasLimit",msg_jsonval, gas_limit_u64,    0);
+        crate::json_val_to_string!( "/GasFeeCap",msg_jsonval, gas_fee_cap_str, "0");
+        crate::json_val_to_string!( "/GasPremium",msg_jsonval, gas_premium_str, "0");
         crate::json_val_to_string!( "/GasPrice",msg_jsonval, gas_price_str,  "0");
         crate::json_val_to_string!( "/Method",  msg_jsonval, method_str,     "0");
         crate::json_val_to_string!( "/Params",  msg_jsonval, params_str,      "");
         self.msg.version   = version_u64;
-        self.msg.to        = to_str;
-        self.msg.from      = from_str;
+        self.msg.to        = parse_address("To", &to_str);
+        self.msg.from      = parse_address("From", &from_str);
         self.msg.nonce     = nonce_u64;
-        self.msg.value     = value_str;
+        self.msg.value     = parse_token_amount("Value", &value_str);
         self.msg.gas_limit = gas_limit_u64;
-        self.msg.gas_price = gas_price_str;
+        self.msg.gas_fee_cap = parse_token_amount("GasFeeCap", &gas_fee_cap_str);
+        self.msg.gas_premium = parse_token_amount("GasPremium", &gas_premium_str);
+        self.msg.gas_price = msg_jsonval.pointer("/GasPrice")
+            .map(|_| parse_token_amount("GasPrice", &gas_price_str));
         self.msg.method    = method_str;
+        self.msg.method_name = well_known_method_name(self.msg.to.as_ref(), self.msg.method_number());
         self.msg.params    = params_str;
         self
     }
@@ -150,6 +326,17 @@ impl MessageBuilder {
         self
     }
 
+    pub fn inclusion<'a>(&'a mut self, included_in: Option<TipsetRef>, included_in_block: Option<String>) -> &'a mut MessageBuilder {
+        self.msg.included_in = included_in;
+        self.msg.included_in_block = included_in_block;
+        self
+    }
+
+    pub fn execution<'a>(&'a mut self, executed_in: Option<TipsetRef>) -> &'a mut MessageBuilder {
+        self.msg.executed_in = executed_in;
+        self
+    }
+
     pub fn get(&mut self) -> Message {
         let mut alt_msg = Message::new();
         std::mem::swap(&mut self.msg, &mut alt_msg);
@@ -157,90 +344,72 @@ impl MessageBuilder {
     }
 }
 
+// The tipset whose blocks are being visited (see BlockAnalyzer::start_tipset())
+struct TipsetContext {
+    tipset: TipsetRef,
+    parent: Option<TipsetRef>,
+    // Every block of a tipset has the same parents, and so the same parent messages; they
+    // are reported with the first block they are fetched for
+    parent_messages_reported: bool,
+}
+
 pub struct BlockAnalyzer<'a> {
     api : &'a api::ApiClient,
-    pub incomplete_msg_cache : HashMap<String,MessageTypeFlag>,
+    incomplete_msg_cache : IncompleteMessageCache,
+    tipset : Option<TipsetContext>,
 }
 
 impl<'a> BlockAnalyzer<'_> {
     pub fn new(api : &'a api::ApiClient) -> BlockAnalyzer {
         BlockAnalyzer{
             api : api,
-            incomplete_msg_cache : HashMap::new(),
+            incomplete_msg_cache : IncompleteMessageCache::default(),
+            tipset : None,
         }
     }
 
-    // combines the results of Filecoin.ChainGetParentMessages and .ChainGetParentReceipts
-    // to create tuple of {msg_cid, message, receipt)}, which is the fully formed message type.
-    fn iterate_over_complete_messages_in_block(&mut self, block_cid: &str, 
-        each_complete_message: fn(msg_cid: &str,  msg: &Message))
-    {
-        // TODO:  check if these are `jsonrpsee::common::JsonValue::Null`; if so, pause and retry
-        // the requset
-        let parent_msgs_jsonval : jsonrpsee::common::JsonValue = self.api.chain_get_parent_messages(block_cid);
-        let parent_receipts_jsonval : jsonrpsee::common::JsonValue = self.api.chain_get_parent_receipts(block_cid);
-        
-        let mut i : u32 = 0;
-        let mut consumed_all_cid_msg_pairs = false;
-        loop {
-            let mut cid_str : String = "".to_string();
-            let mut msg_builder = MessageBuilder::new();
-            let mut msg_jsonval : &jsonrpsee::common::JsonValue = &jsonrpsee::common::JsonValue::Null;
-            let receipt_jsonval : &jsonrpsee::common::JsonValue;
-
-            let cid_msg_json_path = format!("/{}",i);
-            if let Some(cid_msg_jsonval) = parent_msgs_jsonval.pointer(&cid_msg_json_path) {
-                let cid_msg_json_str = cid_msg_jsonval.to_string();
-
-                let json_path = format!("/Cid/~1");
-                if let Some(cid_jsonval) = cid_msg_jsonval.pointer(&json_path) {
-                    cid_str = cid_jsonval.to_string();
-                    cid_str = cid_str[1..cid_str.len()-1].to_string();
-                } else {
-                    eprintln!("error: failed on cid_str extraction from this json blob:\n>>>>>\n'{}'\n<<<<<\n",cid_msg_json_str);
-                    break;
-                }
-
-                let json_path = format!("/Message");
-                if let Some(msg_jsonval_) = cid_msg_jsonval.pointer(&json_path) {
-                    msg_jsonval = msg_jsonval_;
-                } else {
-                    eprintln!("error: failed on msg_jsonval extraction from this json blob:\n>>>>>\n'{}'\n<<<<<\n",cid_msg_json_str);
-                    break;
-                }
-            } else {
-                consumed_all_cid_msg_pairs = true;
-            }
+    // The messages seen included but not executed yet
+    pub fn incomplete_msg_cache(&self) -> &IncompleteMessageCache {
+        &self.incomplete_msg_cache
+    }
 
-            let receipt_json_path = format!("/{}",i);
-            if let Some(receipt_jsonval_) = parent_receipts_jsonval.pointer(&receipt_json_path) {
-                receipt_jsonval = receipt_jsonval_;
-                assert_ne!(consumed_all_cid_msg_pairs,true,"All (cid,msg) pairs consumed but there is >= 1 unconsumed receipt: mismatch");
-            } else {
-                assert_eq!(consumed_all_cid_msg_pairs,true,"All (cid,msg) pairs not consumed yet but no remaining receipts: mismatch");
-                break;
-            }
+    // How many epochs a message may wait for its receipt before start_tipset() gives up on it
+    pub fn set_message_cache_epochs(&mut self, max_epochs: u64) {
+        self.incomplete_msg_cache.set_max_epochs(max_epochs);
+    }
 
-            // Did we cache msg_type_flag on a previous block?
-            let mut msg_type_flag = MessageTypeFlag::Unknown;
-            if self.incomplete_msg_cache.contains_key(&cid_str) {
-                if let Some(mtf) = self.incomplete_msg_cache.remove(&cid_str) {
-                    msg_type_flag = mtf;
-                }
+    // Tells the analyzer that the blocks visited next belong to `tipset`, whose parent
+    // tipset is `parent` (if known).  From then on the messages `tipset` executed are
+    // reported once, with the first of its blocks visited, and with both tipsets attached.
+    // Without it, each block reports its parent messages with no tipsets attached.
+    //
+    // Messages included too long before `tipset` to still be executed are evicted from the
+    // cache and reported to `visitor`'s on_unexecuted_message.  If the receipts of the tipset
+    // before were never looked at (the visitor doesn't want messages, or the error policy
+    // skipped all its blocks), the messages it may have executed are forgotten instead, as
+    // whether they executed isn't known.
+    pub fn start_tipset(&mut self, tipset: TipsetRef, parent: Option<TipsetRef>, visitor: &mut dyn ChainVisitor) {
+        if let Some(last_ctx) = self.tipset.as_ref().filter(|ctx| !ctx.parent_messages_reported) {
+            let forgotten = self.incomplete_msg_cache.forget_older_than(last_ctx.tipset.height);
+            if forgotten > 0 {
+                log::debug!("Forgot {} messages whose receipts at height {} weren't fetched",forgotten,last_ctx.tipset.height);
             }
+        }
+        for (msg_cid, incomplete_msg) in self.incomplete_msg_cache.evict_older_than(tipset.height) {
+            log::debug!("Message {} included at height {} was never executed",msg_cid,incomplete_msg.height);
+            visitor.on_unexecuted_message(&msg_cid, &incomplete_msg);
+        }
+        self.tipset = Some(TipsetContext{ tipset, parent, parent_messages_reported: false });
+    }
 
-            // Make the message struct
-            let message : Message = msg_builder
-                                .msg_fields(msg_jsonval)
-                                .msg_type(msg_type_flag)
-                                .receipt_field(receipt_jsonval)
-                                .get();
-
-            // Invoke callback. If return is true, save message struct
-            each_complete_message(&cid_str, &message);
+    // Forgets the messages first seen in `blocks`, which a reorg dropped
+    pub fn revert_blocks(&mut self, blocks: &[String]) {
+        self.incomplete_msg_cache.remove_blocks(blocks);
+    }
 
-            i += 1;
-        } // loop
+    // Whether the next block visited still has parent messages to report to `visitor`
+    pub(crate) fn wants_parent_messages(&self, visitor: &dyn ChainVisitor) -> bool {
+        visitor.wants_messages() && !self.tipset.as_ref().map(|ctx| ctx.parent_messages_reported).unwrap_or(false)
     }
 
     // We iterate over the messasges in a block in order to add them to a list of messages we expect to 
@@ -249,130 +418,192 @@ impl<'a> BlockAnalyzer<'_> {
     //
     // Gets all the json back from api::chain_get_block_messages then
     // acts once one each {MsgCid,Message} pair found.
-    pub fn iterate_over_all_messages_in_block(&mut self, block_cid: &str, 
-        each_complete_message: std::option::Option<fn(msg_cid: &str,  msg: &Message)>,
-        each_new_message:      std::option::Option<fn(msg_cid: &str)>)
+    pub fn iterate_over_all_messages_in_block<'b>(&mut self, block_cid: &str, 
+        each_complete_message: std::option::Option<&'b mut dyn FnMut(&str, &Message)>,
+        each_new_message:      std::option::Option<&'b mut dyn FnMut(&str)>) -> Result<(), WalkError>
     {
-        // TODO:  check if block_hdrs_jsonval is `jsonrpsee::common::JsonValue::Null`; 
-        // if so, pause and retry the requset
-
-        // get block header and extract BLSAggregate from it
-        let block_hdrs_jsonval : jsonrpsee::common::JsonValue = self.api.chain_get_block(block_cid);
-        let bls_aggregate_type_num : i64;
-        let mut bls_aggregate_data_str : String;
-        if let Some(bls_aggregate_jsonval) = block_hdrs_jsonval.pointer("/BLSAggregate") {
-            // TODO:  bls_aggregate_type should be a uint, not a str
-            if let Some(bls_aggregate_type_jsonval) = bls_aggregate_jsonval.pointer("/Type") {
-                bls_aggregate_type_num = match bls_aggregate_type_jsonval.as_i64() {
-                    Some(n) => { n },
-                    None => { -1 },
-                }
-            } else {
-                bls_aggregate_type_num = -1;
-            }
-            if let Some(bls_aggregate_data_jsonval) = bls_aggregate_jsonval.pointer("/Data") {
-                bls_aggregate_data_str = bls_aggregate_data_jsonval.to_string();
-                bls_aggregate_data_str = bls_aggregate_data_str[1..bls_aggregate_data_str.len()-1].to_string();
-            } else {
-                bls_aggregate_data_str = "".to_string();
-            }
-        } else {
-            bls_aggregate_type_num = -1;
-            bls_aggregate_data_str = "".to_string();
-        }
-        let bls_aggregate_signature : BlsAggregateSignature = BlsAggregateSignature::new(bls_aggregate_type_num, &bls_aggregate_data_str);
-
-
-        // TODO:  check if block_msgs_jsonval is null as above => pause and retry
-
-        // Build vector of all cids in order
-        let block_msgs_jsonval : jsonrpsee::common::JsonValue = self.api.chain_get_block_messages(block_cid);
-        let mut msg_cid : String;
-        let mut vd_msg_cids : std::collections::VecDeque<String> = 
-            std::collections::VecDeque::new();
-        let mut i : i32 = 0;
-        loop {
-            let json_path = format!("/Cids/{}/~1",i);
-            if let Some(msg_cid_jsonval) = block_msgs_jsonval.pointer(&json_path) {
-                msg_cid = msg_cid_jsonval.to_string();
-                msg_cid = msg_cid[1..msg_cid.len()-1].to_string();
-                vd_msg_cids.push_back(msg_cid);
-            } else {
-                break;
-            }
-            i += 1;
-        }
+        self.visit_messages_in_block(block_cid, &mut CallbackVisitor{
+            on_found_new_message: each_complete_message,
+            on_found_new_message_cid: each_new_message,
+            ..CallbackVisitor::none()
+        })
+    }
 
+    // Same as iterate_over_all_messages_in_block(), reporting to `visitor`'s on_message_cid
+    // and on_message.
+    //
+    // Everything is fetched and checked before anything is reported, so on an error the
+    // visitor has seen none of this block's messages and the cache is unchanged.
+    pub fn visit_messages_in_block(&mut self, block_cid: &str, visitor: &mut dyn ChainVisitor) -> Result<(), WalkError>
+    {
+        let block_data = async_std::task::block_on(BlockData::fetch(self.api.as_async(), block_cid, self.wants_parent_messages(visitor)))?;
+        self.visit_block_data(block_cid, &block_data, visitor)
+    }
 
-        // Loop over each bls message
-        i = 0;
-        loop {
-            let msg_type = MessageTypeFlag::BlsMessage(bls_aggregate_signature.copy());
-            let bls_msg_json_path = format!("/BlsMessages/{}",i);
-            if let Some(_bls_msg_jsonval) = block_msgs_jsonval.pointer(&bls_msg_json_path) {
-                if let Some(next_msg_cid) = vd_msg_cids.pop_front() {
-                    if let Some(f) = each_new_message {
-                        f(&next_msg_cid);
-                    }
-                    self.incomplete_msg_cache.insert(next_msg_cid, msg_type);
-                } else {
-                    assert!(false,"vd_msg_cids was empty prematurely: mismatch between number of message CIDs and number of BlsMessages");
-                }
-            } else {
-                break;
-            }
-            i += 1;
-        }
-
-
-        // Loop over each secpk message
-        i = 0;
-        loop {
-            let secpk_signature : SecpkSignature;
-            let secpk_msg_json_path = format!("/SecpkMessages/{}",i);
-            if let Some(secpk_msg_jsonval) = block_msgs_jsonval.pointer(&secpk_msg_json_path) {
-                if let Some(next_msg_cid) = vd_msg_cids.pop_front() {
-                    let mut secp_signature_type_num : i64 = -1;
-                    let mut secp_signature_data_str : String = "".to_string();
-                    if let Some(secpk_signature_jsonval) = secpk_msg_jsonval.pointer("/Signature") {
-                        if let Some(secpk_signature_type_jsonval) = secpk_signature_jsonval.pointer("/Type") {
-                            secp_signature_type_num = match secpk_signature_type_jsonval.as_i64() {
-                                Some(n)=>{n},
-                                None=>{-1},
-                            }
-                        }
-                        if let Some(secpk_signature_data_jsonval) = secpk_signature_jsonval.pointer("/Data") {
-                            secp_signature_data_str = secpk_signature_data_jsonval.to_string();
-                            if secp_signature_data_str.len()>2 {
-                                secp_signature_data_str = secp_signature_data_str[1..secp_signature_data_str.len()-1].to_string();
-                            }
-                        }
-                    }
-                    secpk_signature = SecpkSignature::new(secp_signature_type_num, &secp_signature_data_str);
-                    let msg_type = MessageTypeFlag::SecpMessage(secpk_signature);
-                    if let Some(f) = each_new_message {
-                        f(&next_msg_cid);
-                    }
-                    self.incomplete_msg_cache.insert(next_msg_cid, msg_type);
-                } else {
-                    assert!(false,"vd_msg_cids was empty prematurely: mismatch between number of message CIDs and number of BlsMessages+SecpkMessages");
-                }
-            } else {
-                break;
+    // The analysis half of visit_messages_in_block(), on data already fetched
+    pub(crate) fn visit_block_data(&mut self, block_cid: &str, block_data: &BlockData, visitor: &mut dyn ChainVisitor) -> Result<(), WalkError>
+    {
+        let new_msgs = new_messages_in_block(block_cid, &block_data.block_hdr, &block_data.block_msgs)?;
+        let complete_msgs = match (&block_data.parents, self.wants_parent_messages(visitor)) {
+            (Some((parent_msgs, parent_receipts)), true) => Some(parent_messages_with_receipts(block_cid, parent_msgs, parent_receipts)?),
+            _ => None,
+        };
+
+        // a message included by several blocks is reported with the first
+        let height = self.tipset.as_ref().map(|ctx| ctx.tipset.height).unwrap_or(0);
+        for (msg_cid, msg_type) in new_msgs {
+            let incomplete_msg = IncompleteMessage{ msg_type, block_cid: block_cid.to_string(), height };
+            if self.incomplete_msg_cache.insert(msg_cid.clone(), incomplete_msg) {
+                visitor.on_message_cid(&msg_cid);
             }
-            i += 1;
         }
 
-        //
-        // Iterate the parents_messages and parents_receipts parts of this block (can skip if no callback)
-        //
-        if let Some(f) = each_complete_message {
-            self.iterate_over_complete_messages_in_block(block_cid, f);
+        // combine each parent message with its receipt (and the signature cached when it
+        // was first seen) to create the fully formed message type
+        let complete_msgs = match complete_msgs {
+            Some(complete_msgs) => complete_msgs,
+            None => return Ok(()),
+        };
+        let (executed_in, included_in) = match self.tipset.as_mut() {
+            Some(ctx) => {
+                ctx.parent_messages_reported = true;
+                (Some(ctx.tipset.clone()), ctx.parent.clone())
+            },
+            None => (None, None),
+        };
+        for (cid_str, msg_jsonval, receipt_jsonval) in complete_msgs {
+            let (msg_type_flag, included_in_block) = match self.incomplete_msg_cache.remove(&cid_str) {
+                Some(incomplete_msg) => (incomplete_msg.msg_type, Some(incomplete_msg.block_cid)),
+                None => (MessageTypeFlag::Unknown, None),
+            };
+            let message : Message = MessageBuilder::new()
+                                .msg_fields(msg_jsonval)
+                                .msg_type(msg_type_flag)
+                                .receipt_field(receipt_jsonval)
+                                .inclusion(included_in.clone(), included_in_block)
+                                .execution(executed_in.clone())
+                                .get();
+            visitor.on_message(&cid_str, &message);
         }
+        Ok(())
+    }
+}
+
+// The api responses BlockAnalyzer works from for one block
+pub(crate) struct BlockData {
+    block_hdr:          jsonrpsee::common::JsonValue,
+    block_msgs:         jsonrpsee::common::JsonValue,
+    // The .ChainGetParentMessages and .ChainGetParentReceipts responses, if fetched
+    parents:            Option<(jsonrpsee::common::JsonValue, jsonrpsee::common::JsonValue)>,
+}
+
+impl BlockData {
+    // The parent messages and receipts are only fetched if `with_parent_messages`
+    pub(crate) async fn fetch(api: &api::AsyncApiClient, block_cid: &str, with_parent_messages: bool) -> Result<BlockData, WalkError> {
+        let block_hdr = api.chain_get_block(block_cid).await
+            .map_err(|e| WalkError::api("chain_get_block", e))?;
+        let block_msgs = api.chain_get_block_messages(block_cid).await
+            .map_err(|e| WalkError::api("chain_get_block_messages", e))?;
+        let mut block_data = BlockData{ block_hdr, block_msgs, parents: None };
+        if with_parent_messages {
+            let parent_msgs = api.chain_get_parent_messages(block_cid).await
+                .map_err(|e| WalkError::api("chain_get_parent_messages", e))?;
+            let parent_receipts = api.chain_get_parent_receipts(block_cid).await
+                .map_err(|e| WalkError::api("chain_get_parent_receipts", e))?;
+            block_data.parents = Some((parent_msgs, parent_receipts));
+        }
+        Ok(block_data)
+    }
+
+    pub(crate) fn has_parent_messages(&self) -> bool {
+        self.parents.is_some()
+    }
+}
+
+// Lotus sends nil slices as null, so a missing or null list is an empty one
+fn json_array<'j>(block_cid: &str, jsonval: Option<&'j jsonrpsee::common::JsonValue>) -> Result<&'j [jsonrpsee::common::JsonValue], WalkError> {
+    match jsonval {
+        None | Some(jsonrpsee::common::JsonValue::Null) => Ok(&[]),
+        Some(jsonrpsee::common::JsonValue::Array(items)) => Ok(items),
+        Some(other) => Err(WalkError::MalformedMessage{ block_cid: block_cid.to_string(), json: other.to_string() }),
+    }
+}
+
+// The (msg_cid, signature) of each message first included in a block.  The Cids list of
+// a Filecoin.ChainGetBlockMessages response has the BLS messages' cids first, then the secp
+// messages', so the counts must match exactly.
+fn new_messages_in_block(block_cid: &str, block_hdrs_jsonval: &jsonrpsee::common::JsonValue,
+    block_msgs_jsonval: &jsonrpsee::common::JsonValue) -> Result<Vec<(String, MessageTypeFlag)>, WalkError>
+{
+    // extract BLSAggregate from the block header
+    let bls_aggregate_type_num : i64 = block_hdrs_jsonval.pointer("/BLSAggregate/Type")
+        .and_then(|jsonval| jsonval.as_i64())
+        .unwrap_or(-1);
+    let bls_aggregate_data_str : &str = block_hdrs_jsonval.pointer("/BLSAggregate/Data")
+        .and_then(|jsonval| jsonval.as_str())
+        .unwrap_or("");
+    let bls_aggregate_signature : BlsAggregateSignature = BlsAggregateSignature::new(bls_aggregate_type_num, bls_aggregate_data_str);
+
+    let msg_cids = json_array(block_cid, block_msgs_jsonval.pointer("/Cids"))?;
+    let bls_msgs = json_array(block_cid, block_msgs_jsonval.pointer("/BlsMessages"))?;
+    let secpk_msgs = json_array(block_cid, block_msgs_jsonval.pointer("/SecpkMessages"))?;
+    if msg_cids.len() != bls_msgs.len() + secpk_msgs.len() {
+        return Err(WalkError::MessageCountMismatch{
+            block_cid: block_cid.to_string(),
+            cids: msg_cids.len(),
+            messages: bls_msgs.len() + secpk_msgs.len(),
+        });
+    }
+
+    let mut new_msgs : Vec<(String, MessageTypeFlag)> = Vec::with_capacity(msg_cids.len());
+    for (i, msg_cid_jsonval) in msg_cids.iter().enumerate() {
+        let msg_cid = match msg_cid_jsonval.pointer("/~1").and_then(|jsonval| jsonval.as_str()) {
+            Some(msg_cid) => msg_cid.to_string(),
+            None => return Err(WalkError::MalformedMessage{ block_cid: block_cid.to_string(), json: msg_cid_jsonval.to_string() }),
+        };
+        let msg_type = if i < bls_msgs.len() {
+            MessageTypeFlag::BlsMessage(bls_aggregate_signature.copy())
+        } else {
+            let secpk_msg_jsonval = &secpk_msgs[i - bls_msgs.len()];
+            let secp_signature_type_num : i64 = secpk_msg_jsonval.pointer("/Signature/Type")
+                .and_then(|jsonval| jsonval.as_i64())
+                .unwrap_or(-1);
+            let secp_signature_data_str : &str = secpk_msg_jsonval.pointer("/Signature/Data")
+                .and_then(|jsonval| jsonval.as_str())
+                .unwrap_or("");
+            MessageTypeFlag::SecpMessage(SecpkSignature::new(secp_signature_type_num, secp_signature_data_str))
+        };
+        new_msgs.push((msg_cid, msg_type));
+    }
+    Ok(new_msgs)
+}
 
-        // assert that no msg_cids remain in queue
-        assert_eq!(vd_msg_cids.len(),0,"All CIDs must now be exactly consumed");
+// Pairs each {Cid, Message} entry of a Filecoin.ChainGetParentMessages response with the
+// receipt at the same index of the .ChainGetParentReceipts response.
+fn parent_messages_with_receipts<'j>(block_cid: &str, parent_msgs_jsonval: &'j jsonrpsee::common::JsonValue,
+    parent_receipts_jsonval: &'j jsonrpsee::common::JsonValue) 
+    -> Result<Vec<(String, &'j jsonrpsee::common::JsonValue, &'j jsonrpsee::common::JsonValue)>, WalkError>
+{
+    let parent_msgs = json_array(block_cid, Some(parent_msgs_jsonval))?;
+    let parent_receipts = json_array(block_cid, Some(parent_receipts_jsonval))?;
+    if parent_msgs.len() != parent_receipts.len() {
+        return Err(WalkError::ReceiptCountMismatch{
+            block_cid: block_cid.to_string(),
+            messages: parent_msgs.len(),
+            receipts: parent_receipts.len(),
+        });
     }
+
+    let mut complete_msgs = Vec::with_capacity(parent_msgs.len());
+    for (cid_msg_jsonval, receipt_jsonval) in parent_msgs.iter().zip(parent_receipts) {
+        let cid_str = cid_msg_jsonval.pointer("/Cid/~1").and_then(|jsonval| jsonval.as_str());
+        let msg_jsonval = cid_msg_jsonval.pointer("/Message");
+        match (cid_str, msg_jsonval) {
+            (Some(cid_str), Some(msg_jsonval)) => complete_msgs.push((cid_str.to_string(), msg_jsonval, receipt_jsonval)),
+            _ => return Err(WalkError::MalformedMessage{ block_cid: block_cid.to_string(), json: cid_msg_jsonval.to_string() }),
+        }
+    }
+    Ok(complete_msgs)
 }
 
 
@@ -384,6 +615,8 @@ impl<'a> BlockAnalyzer<'_> {
 
 pub struct Tipsets {
     i : usize,
+    // the height asked for, which the returned tipset's Height is below for a null round
+    requested_height : u64,
     // TODO:  json_val could be null; if so, pause and retry the request
     json_val : jsonrpsee::common::JsonValue,
 }
@@ -392,8 +625,47 @@ impl Tipsets {
     pub fn new(api : &api::ApiClient, height: u64) -> Tipsets {
         Tipsets{
             i : 0,
+            requested_height : height,
             // TODO:  json_val could be null; if so, pause and retry the request
-            json_val: api.chain_get_tipset_by_height(height)
+            json_val: json_or_null("chain_get_tipset_by_height", api.chain_get_tipset_by_height(height))
+        }
+    }
+
+    pub fn try_new(api : &api::ApiClient, height: u64) -> Result<Tipsets, LotusError> {
+        Ok(Tipsets::from_json(height, api.chain_get_tipset_by_height(height)?))
+    }
+
+    // From a Filecoin.ChainGetTipSetByHeight response for `height` fetched elsewhere
+    pub(crate) fn from_json(height: u64, json_val: jsonrpsee::common::JsonValue) -> Tipsets {
+        Tipsets{
+            i : 0,
+            requested_height : height,
+            json_val,
+        }
+    }
+
+    // The Height of the tipset returned, or None if the request failed
+    pub fn height(&self) -> Option<u64> {
+        self.json_val.pointer("/Height").and_then(|jsonval| jsonval.as_u64())
+    }
+
+    // True if no blocks were mined at the requested height.  For a null round
+    // ChainGetTipSetByHeight returns the closest tipset below it instead, which this
+    // iterator then yields the blocks of.
+    pub fn is_null_round(&self) -> bool {
+        self.height().map(|height| height < self.requested_height).unwrap_or(false)
+    }
+
+    // The whole tipset (height, key, block headers), or None if the request failed.
+    pub fn tipset(&self) -> Option<crate::types::TipSet> {
+        match serde_json::from_value(self.json_val.clone()) {
+            Ok(tipset) => Some(tipset),
+            Err(e) => {
+                if !self.json_val.is_null() {
+                    log::error!("Tipsets::tipset: unparseable tipset: {}",e);
+                }
+                None
+            }
         }
     }
 }
@@ -438,9 +710,16 @@ impl ChainHeadBlocks {
         ChainHeadBlocks{
             i : 0,
             // TODO:  json_val could be null; if so, pause and retry the request
-            json_val: api.chain_head()
+            json_val: json_or_null("chain_head", api.chain_head())
         }
     }
+
+    pub fn try_new(api : &api::ApiClient) -> Result<ChainHeadBlocks, LotusError> {
+        Ok(ChainHeadBlocks{
+            i : 0,
+            json_val: api.chain_head()?,
+        })
+    }
 }
 
 impl Iterator for ChainHeadBlocks {
@@ -484,6 +763,298 @@ impl MaxTipsetHeight {
             max_height : max_height,
         }
     }
+
+    pub fn try_new(api : &api::ApiClient) -> Result<MaxTipsetHeight, LotusError> {
+        let max_height = ChainHeadBlocks::try_new(api)?
+            .map(|head_block| { head_block.height })
+            .max()
+            .unwrap_or(0u64);
+        Ok(MaxTipsetHeight{
+            max_height : max_height,
+        })
+    }
+}
+
+////////////////////////////////////////////////////////
+/// 
+/// FollowMode / CancelHandle
+/// 
+////////////////////////////////////////////////////////
+
+// How follow_blockchain() learns that the chain has grown once it has caught up to the head.
+#[derive(Debug, Clone)]
+pub enum FollowMode {
+    // Re-query the head height every so often
+    Poll(std::time::Duration),
+    // Wait for Filecoin.ChainNotify head changes over a websocket
+    Subscribe,
+}
+
+// Stops a follow_blockchain() (or iterate_over_blockchain()) walk from another thread.  The
+// walk finishes the tipset it is on, so callbacks always see complete tipsets.
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle {
+    cancelled: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl CancelHandle {
+    pub fn new() -> CancelHandle {
+        CancelHandle::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
+
+// While waiting on the head, how often to check for cancellation
+const CANCEL_CHECK_INTERVAL : std::time::Duration = std::time::Duration::from_millis(250);
+// How long to wait before re-subscribing after ChainNotify failed
+const RESUBSCRIBE_DELAY : std::time::Duration = std::time::Duration::from_secs(5);
+
+// Sleeps for `duration`, waking early if cancelled.  Returns false if cancelled.
+fn sleep_unless_cancelled(duration: std::time::Duration, cancel: &CancelHandle) -> bool {
+    let start = std::time::Instant::now();
+    while !cancel.is_cancelled() {
+        let elapsed = start.elapsed();
+        if elapsed >= duration {
+            return true;
+        }
+        std::thread::sleep(std::cmp::min(CANCEL_CHECK_INTERVAL, duration - elapsed));
+    }
+    false
+}
+
+enum HeadWaiter {
+    Poll(std::time::Duration),
+    Subscribe(Option<crate::ws::Subscription<Vec<crate::types::HeadChange>>>),
+}
+
+impl HeadWaiter {
+    fn new(follow_mode: &FollowMode) -> HeadWaiter {
+        match follow_mode {
+            FollowMode::Poll(interval) => HeadWaiter::Poll(*interval),
+            FollowMode::Subscribe => HeadWaiter::Subscribe(None),
+        }
+    }
+
+    // Blocks until the head is above `height`, returning the new head height, or None
+    // if cancelled first.
+    fn wait_for_head_above(&mut self, api: &api::ApiClient, height: u64, cancel: &CancelHandle) -> Option<u64> {
+        use futures::StreamExt;
+        use crate::types::HeadChangeType;
+
+        match self {
+            HeadWaiter::Poll(interval) => {
+                loop {
+                    if !sleep_unless_cancelled(*interval, cancel) {
+                        return None;
+                    }
+                    let max_height = MaxTipsetHeight::new(api).max_height;
+                    if max_height > height {
+                        return Some(max_height);
+                    }
+                }
+            },
+            HeadWaiter::Subscribe(subscription) => {
+                loop {
+                    if cancel.is_cancelled() {
+                        return None;
+                    }
+                    let head_changes = match subscription {
+                        Some(head_changes) => head_changes,
+                        None => {
+                            match async_std::task::block_on(api.as_async().chain_notify()) {
+                                Ok(head_changes) => subscription.insert(head_changes),
+                                Err(e) => {
+                                    log::error!("wait_for_head_above: ChainNotify subscription failed: {}",e);
+                                    if !sleep_unless_cancelled(RESUBSCRIBE_DELAY, cancel) {
+                                        return None;
+                                    }
+                                    continue;
+                                }
+                            }
+                        }
+                    };
+                    let next = async_std::task::block_on(
+                        async_std::future::timeout(CANCEL_CHECK_INTERVAL, head_changes.next()));
+                    match next {
+                        Err(_) => {},
+                        Ok(None) => {
+                            log::info!("wait_for_head_above: ChainNotify subscription ended; re-subscribing");
+                            *subscription = None;
+                        },
+                        Ok(Some(Err(e))) => {
+                            log::error!("wait_for_head_above: bad ChainNotify notification: {}",e);
+                        },
+                        Ok(Some(Ok(changes))) => {
+                            let new_height = changes.iter()
+                                .filter(|change| change.change_type != HeadChangeType::Revert)
+                                .map(|change| change.val.height)
+                                .max();
+                            if let Some(new_height) = new_height {
+                                if new_height > height {
+                                    return Some(new_height);
+                                }
+                            }
+                        },
+                    }
+                }
+            },
+        }
+    }
+}
+
+////////////////////////////////////////////////////////
+/// 
+/// ErrorPolicy
+/// 
+////////////////////////////////////////////////////////
+
+// What the walker does when processing a block (or fetching a tipset) fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorAction {
+    // Stop the walk and return the error
+    Abort,
+    // Log the error and go on with the next block, without reporting any of this block's
+    // messages.  A tipset that can't be fetched can't be skipped, so there this is Abort;
+    // for a checkpoint that can't be saved, the walk goes on without saving it.
+    SkipBlock,
+    // Try again up to `attempts` more times, `delay` apart, then Abort
+    Retry { attempts: u32, delay: std::time::Duration },
+}
+
+// The ErrorAction for each kind of WalkError.  By default failed api calls are retried
+// (3 more times, 5s apart), inconsistent or malformed blocks are skipped and checkpoint
+// errors abort.
+#[derive(Debug, Clone)]
+pub struct ErrorPolicy {
+    api:                        ErrorAction,
+    message_count_mismatch:     ErrorAction,
+    receipt_count_mismatch:     ErrorAction,
+    malformed_message:          ErrorAction,
+    checkpoint:                 ErrorAction,
+}
+
+impl Default for ErrorPolicy {
+    fn default() -> ErrorPolicy {
+        ErrorPolicy{
+            api: ErrorAction::Retry{ attempts: 3, delay: std::time::Duration::from_secs(5) },
+            message_count_mismatch: ErrorAction::SkipBlock,
+            receipt_count_mismatch: ErrorAction::SkipBlock,
+            malformed_message: ErrorAction::SkipBlock,
+            checkpoint: ErrorAction::Abort,
+        }
+    }
+}
+
+impl ErrorPolicy {
+    // Every kind of error aborts the walk
+    pub fn abort_on_all() -> ErrorPolicy {
+        ErrorPolicy{
+            api: ErrorAction::Abort,
+            message_count_mismatch: ErrorAction::Abort,
+            receipt_count_mismatch: ErrorAction::Abort,
+            malformed_message: ErrorAction::Abort,
+            checkpoint: ErrorAction::Abort,
+        }
+    }
+
+    pub fn on(mut self, kind: WalkErrorKind, action: ErrorAction) -> ErrorPolicy {
+        match kind {
+            WalkErrorKind::Api => self.api = action,
+            WalkErrorKind::MessageCountMismatch => self.message_count_mismatch = action,
+            WalkErrorKind::ReceiptCountMismatch => self.receipt_count_mismatch = action,
+            WalkErrorKind::MalformedMessage => self.malformed_message = action,
+            WalkErrorKind::Checkpoint => self.checkpoint = action,
+        }
+        self
+    }
+
+    pub fn action(&self, kind: WalkErrorKind) -> ErrorAction {
+        match kind {
+            WalkErrorKind::Api => self.api,
+            WalkErrorKind::MessageCountMismatch => self.message_count_mismatch,
+            WalkErrorKind::ReceiptCountMismatch => self.receipt_count_mismatch,
+            WalkErrorKind::MalformedMessage => self.malformed_message,
+            WalkErrorKind::Checkpoint => self.checkpoint,
+        }
+    }
+}
+
+// How a walk (walk_blockchain(), follow_blockchain(), walker::ChainWalker) handles errors,
+// how far it fetches ahead and where it keeps its checkpoint.
+#[derive(Clone)]
+pub struct WalkOptions {
+    error_policy:       ErrorPolicy,
+    prefetch:           usize,
+    checkpoint_store:   Option<SharedCheckpointStore>,
+    message_cache_epochs: u64,
+}
+
+impl Default for WalkOptions {
+    fn default() -> WalkOptions {
+        WalkOptions{
+            error_policy: ErrorPolicy::default(),
+            prefetch: 1,
+            checkpoint_store: None,
+            message_cache_epochs: DEFAULT_MESSAGE_CACHE_EPOCHS,
+        }
+    }
+}
+
+impl std::fmt::Debug for WalkOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("WalkOptions")
+            .field("error_policy", &self.error_policy)
+            .field("prefetch", &self.prefetch)
+            .field("checkpoint_store", &self.checkpoint_store.is_some())
+            .field("message_cache_epochs", &self.message_cache_epochs)
+            .finish()
+    }
+}
+
+impl WalkOptions {
+    pub fn error_policy(mut self, error_policy: ErrorPolicy) -> WalkOptions {
+        self.error_policy = error_policy;
+        self
+    }
+
+    // Fetch up to `heights` heights ahead of the walk in parallel, each over its own
+    // connection to the node.  Events are still delivered in height order.  With 1 (the
+    // default) each height is fetched only when the walk reaches it.
+    pub fn prefetch(mut self, heights: usize) -> WalkOptions {
+        self.prefetch = heights.max(1);
+        self
+    }
+
+    // Save a Checkpoint to `store` after each tipset, and on starting, resume just above the
+    // one saved (if it is at or above `iterate_from_min_height` - 1).  A checkpointed tipset
+    // that a reorg dropped while the walk wasn't running is reported with on_revert_tipset.
+    pub fn checkpoint_store<S: CheckpointStore + Send + 'static>(mut self, store: S) -> WalkOptions {
+        self.checkpoint_store = Some(std::sync::Arc::new(std::sync::Mutex::new(store)));
+        self
+    }
+
+    // checkpoint_store() with a FileCheckpointStore at `path`
+    pub fn checkpoint_file<P: AsRef<std::path::Path>>(self, path: P) -> WalkOptions {
+        self.checkpoint_store(FileCheckpointStore::new(path))
+    }
+
+    // Give up on a message that no tipset executed within `epochs` of the one including it,
+    // and report it with on_unexecuted_message (default msgcache::DEFAULT_MESSAGE_CACHE_EPOCHS)
+    pub fn message_cache_epochs(mut self, epochs: u64) -> WalkOptions {
+        self.message_cache_epochs = epochs;
+        self
+    }
+
+    pub(crate) fn has_checkpoint_store(&self) -> bool {
+        self.checkpoint_store.is_some()
+    }
 }
 
 ////////////////////////////////////////////////////////
@@ -492,68 +1063,723 @@ impl MaxTipsetHeight {
 /// 
 ////////////////////////////////////////////////////////
 
-pub fn iterate_over_blockchain(
+pub fn iterate_over_blockchain<'a>(
     iterate_from_min_height:    u64,
     iterate_to_max_height:      u64,
     api:                        &crate::api::ApiClient, 
-    on_starting_new_tipset:     std::option::Option<fn(height: u64, blocks: &Vec<String>)>,
-    on_starting_block:          std::option::Option<fn(blk_cid: &str)>,
-    on_found_new_message_cid:   std::option::Option<fn(msg_cid: &str)>,
-    on_found_new_message:       std::option::Option<fn(msg_cid: &str, msg: &Message)>,
-    on_finished_block:          std::option::Option<fn(blk_cid: &str)>,
-    on_finished_tipset:         std::option::Option<fn(height: u64)>) 
+    on_starting_new_tipset:     std::option::Option<&'a mut dyn FnMut(u64, &Vec<String>)>,
+    on_starting_block:          std::option::Option<&'a mut dyn FnMut(&str)>,
+    on_found_new_message_cid:   std::option::Option<&'a mut dyn FnMut(&str)>,
+    on_found_new_message:       std::option::Option<&'a mut dyn FnMut(&str, &Message)>,
+    on_finished_block:          std::option::Option<&'a mut dyn FnMut(&str)>,
+    on_finished_tipset:         std::option::Option<&'a mut dyn FnMut(u64)>,
+    on_revert_tipset:           std::option::Option<&'a mut dyn FnMut(u64, &Vec<String>)>) -> Result<(), WalkError>
 {
-    //
-    // Construct block analyzer
-    //
-    let mut block_analyzer = BlockAnalyzer::new(&api);
+    // Later events (on_null_round, ...) are only reported to a ChainVisitor; see walk_blockchain()
+    walk_blockchain(iterate_from_min_height, iterate_to_max_height, api, &WalkOptions::default(),
+        &mut CallbackVisitor{
+            on_starting_new_tipset, on_starting_block, on_found_new_message_cid,
+            on_found_new_message, on_finished_block, on_finished_tipset, on_revert_tipset,
+            ..CallbackVisitor::none()
+        })
+}
 
-    //
-    // Iterate over the range of heights
-    //
-    let mut curr_tipset_height = MaxTipsetHeight::new(&api).max_height;
-    log::debug!("current largest tipset height: {})",curr_tipset_height);
-    use std::cmp::{min,max};
-    let mut i : u64 = max(iterate_from_min_height,0 as u64);
-    log::info!("Iterating from height {} to {}",i,min(iterate_to_max_height,curr_tipset_height));
-    loop {
-        let ts_strings : Vec<String> = Tipsets::new(&api,i).collect();
-        if let Some(f) = on_starting_new_tipset {
-            f(i,&ts_strings);
+// Like iterate_over_blockchain(), but reports to a visitor::ChainVisitor instead of a list of
+// callbacks.
+//
+// If the chain reorganizes under the walk (so the tipset at the next height is not a child of
+// the last one visited), on_revert_tipset is called for each abandoned tipset, newest first,
+// and the walk resumes on the new branch just above the fork point.
+//
+// A height where no blocks were mined (a null round) gets only on_null_round, not the
+// on_tipset_start/on_tipset_end of the tipset below it that ChainGetTipSetByHeight returns.
+//
+// Errors are handled as `options`' ErrorPolicy says; the walk stops at the first one it says
+// to abort on, and returns it.
+pub fn walk_blockchain(
+    iterate_from_min_height:    u64,
+    iterate_to_max_height:      u64,
+    api:                        &crate::api::ApiClient, 
+    options:                    &WalkOptions,
+    visitor:                    &mut dyn ChainVisitor) -> Result<(), WalkError>
+{
+    walk_heights(iterate_from_min_height, Some(iterate_to_max_height), api, None, &CancelHandle::new(), options, visitor)
+}
+
+// Like walk_blockchain() but never stops at the head:  once caught up it waits for new
+// tipsets as `follow_mode` says and keeps reporting to `visitor` until `cancel` is cancelled.
+pub fn follow_blockchain(
+    iterate_from_min_height:    u64,
+    api:                        &crate::api::ApiClient, 
+    follow_mode:                FollowMode,
+    cancel:                     &CancelHandle,
+    options:                    &WalkOptions,
+    visitor:                    &mut dyn ChainVisitor) -> Result<(), WalkError>
+{
+    walk_heights(iterate_from_min_height, None, api, Some(&follow_mode), cancel, options, visitor)
+}
+
+// Walks heights from `iterate_from_min_height` up to `iterate_to_max_height` (or forever,
+// if None), following the head as `follow_mode` says once caught up (or stopping there, if
+// None).
+fn walk_heights(
+    iterate_from_min_height:    u64,
+    iterate_to_max_height:      Option<u64>,
+    api:                        &crate::api::ApiClient, 
+    follow_mode:                Option<&FollowMode>,
+    cancel:                     &CancelHandle,
+    options:                    &WalkOptions,
+    visitor:                    &mut dyn ChainVisitor) -> Result<(), WalkError>
+{
+    let mut height_walker = HeightWalker::new(iterate_from_min_height, iterate_to_max_height, api, follow_mode, cancel, options);
+    while height_walker.step(visitor)? {
+        height_walker.commit_checkpoint()?;
+    }
+    log::info!("Walk done; message cache: {:?}",height_walker.message_cache_metrics());
+    Ok(())
+}
+
+// The state of a walk between heights, so it can be driven one height at a time (by
+// walk_heights() or by walker::ChainWalker).
+pub(crate) struct HeightWalker<'a> {
+    api:                        &'a crate::api::ApiClient,
+    block_analyzer:             BlockAnalyzer<'a>,
+    head_waiter:                Option<HeadWaiter>,
+    cancel:                     CancelHandle,
+    error_policy:               ErrorPolicy,
+    prefetcher:                 Option<crate::prefetch::Prefetcher>,
+    checkpoint_store:           Option<SharedCheckpointStore>,
+    checkpoint_loaded:          bool,
+    // Saved by commit_checkpoint(), once the tipset's events have all been handled
+    uncommitted_checkpoint:     Option<Checkpoint>,
+
+    // Tipsets visited so far (height, key), oldest first, to spot and unwind reorgs
+    visited:                    std::collections::VecDeque<(u64, crate::types::TipSetKey)>,
+
+    iterate_to_max_height:      u64,
+    curr_tipset_height:         u64,
+    i:                          u64,
+}
+
+impl<'a> HeightWalker<'a> {
+    pub(crate) fn new(
+        iterate_from_min_height:    u64,
+        iterate_to_max_height:      Option<u64>,
+        api:                        &'a crate::api::ApiClient, 
+        follow_mode:                Option<&FollowMode>,
+        cancel:                     &CancelHandle,
+        options:                    &WalkOptions) -> HeightWalker<'a>
+    {
+        let iterate_to_max_height = iterate_to_max_height.unwrap_or(u64::MAX);
+        log::info!("Iterating from height {} to {}",iterate_from_min_height,iterate_to_max_height);
+        let mut block_analyzer = BlockAnalyzer::new(&api);
+        block_analyzer.set_message_cache_epochs(options.message_cache_epochs);
+        HeightWalker{
+            api,
+            block_analyzer,
+            head_waiter: follow_mode.map(HeadWaiter::new),
+            cancel: cancel.clone(),
+            error_policy: options.error_policy.clone(),
+            prefetcher: new_prefetcher(api, options),
+            checkpoint_store: options.checkpoint_store.clone(),
+            checkpoint_loaded: false,
+            uncommitted_checkpoint: None,
+            visited: std::collections::VecDeque::new(),
+            iterate_to_max_height,
+            // not known yet; the first step() looks it up
+            curr_tipset_height: 0,
+            i: iterate_from_min_height,
         }
-        for blk_cid in ts_strings {
-            if let Some(f) = on_starting_block {
-                f(&blk_cid);
-            }
+    }
 
-            log::info!("Height {} : blk_cid {}...",i,blk_cid);
+    pub(crate) fn set_options(&mut self, options: &WalkOptions) {
+        self.error_policy = options.error_policy.clone();
+        self.prefetcher = new_prefetcher(self.api, options);
+        self.checkpoint_store = options.checkpoint_store.clone();
+        self.block_analyzer.set_message_cache_epochs(options.message_cache_epochs);
+    }
 
-            // Iterate complete messages referenced in this block, and cids of new messages first
-            // appearing in this block.
-            if on_found_new_message.is_some() || on_found_new_message_cid.is_some()
-            {
-                block_analyzer.iterate_over_all_messages_in_block(&blk_cid, on_found_new_message, 
-                    on_found_new_message_cid);
+    pub(crate) fn message_cache_metrics(&self) -> MessageCacheMetrics {
+        self.block_analyzer.incomplete_msg_cache().metrics()
+    }
+
+    // Saves the checkpoint for the last tipset finished, if any
+    pub(crate) fn commit_checkpoint(&mut self) -> Result<(), WalkError> {
+        if let (Some(store), Some(checkpoint)) = (&self.checkpoint_store, self.uncommitted_checkpoint.take()) {
+            apply_error_policy(&self.error_policy, &self.cancel, true,
+                || lock_checkpoint_store(store).save(&checkpoint))?;
+        }
+        Ok(())
+    }
+
+    // Loads the checkpoint and, if the walk should resume from it, moves the walk just above
+    // it.  If the checkpointed tipset is no longer on the chain, walks back from it to the
+    // first tipset that is, reporting each one it passes as reverted, and resumes above that.
+    fn resume_from_checkpoint(&mut self, visitor: &mut dyn ChainVisitor) -> Result<(), WalkError> {
+        let store = match &self.checkpoint_store {
+            Some(store) => store.clone(),
+            None => return Ok(()),
+        };
+        let checkpoint = apply_error_policy(&self.error_policy, &self.cancel, false,
+            || lock_checkpoint_store(&store).load())?;
+        let checkpoint = match checkpoint {
+            Some(Some(checkpoint)) if checkpoint.height + 1 >= self.i => checkpoint,
+            _ => return Ok(()),
+        };
+        log::info!("Resuming after checkpoint at height {}",checkpoint.height);
+
+        let api = self.api;
+        let mut cursor = match apply_error_policy(&self.error_policy, &self.cancel, false,
+            || api.chain_get_tipset_typed(&checkpoint.tipset_key).map_err(|e| WalkError::api("chain_get_tipset", e)))? {
+            Some(tipset) => tipset,
+            None => return Ok(()),
+        };
+        let mut resume_height = checkpoint.height;
+        for _ in 0..REORG_HISTORY_LEN {
+            let canonical = match apply_error_policy(&self.error_policy, &self.cancel, false,
+                || api.chain_get_tipset_by_height_typed(cursor.height).map_err(|e| WalkError::api("chain_get_tipset_by_height", e)))? {
+                Some(tipset) => tipset,
+                None => return Ok(()),
+            };
+            if canonical.key() == cursor.key() {
+                break
             }
+            log::info!("Checkpointed tipset at height {} is no longer on the chain; reverting it",cursor.height);
+            let blocks : Vec<String> = cursor.cids.iter().map(|cid| cid.to_string()).collect();
+            visitor.on_revert_tipset(cursor.height,&blocks);
+            let parents = cursor.parents();
+            cursor = match apply_error_policy(&self.error_policy, &self.cancel, false,
+                || api.chain_get_tipset_typed(&parents).map_err(|e| WalkError::api("chain_get_tipset", e)))? {
+                Some(tipset) => tipset,
+                None => return Ok(()),
+            };
+            resume_height = cursor.height;
+            self.uncommitted_checkpoint = Some(Checkpoint{ height: cursor.height, tipset_key: cursor.key() });
+        }
+
+        self.visited.clear();
+        self.visited.push_back((cursor.height, cursor.key()));
+        self.i = resume_height + 1;
+        Ok(())
+    }
+
+    // Visits the next height (or, after a reorg, reports the reverted tipsets), waiting for
+    // the head to grow first if caught up and following.  Returns Ok(false) once the walk is
+    // over, or the error the policy said to abort on.
+    pub(crate) fn step(&mut self, visitor: &mut dyn ChainVisitor) -> Result<bool, WalkError> {
+        use std::cmp::min;
+        let api = self.api;
+        let i = self.i;
 
-            if let Some(f) = on_finished_block {
-                f(&blk_cid);
+        //
+        // Loop control
+        //
+        if self.cancel.is_cancelled() {
+            log::info!("Walk cancelled before height {}",i);
+            return Ok(false)
+        }
+        if !self.checkpoint_loaded {
+            self.checkpoint_loaded = true;
+            self.resume_from_checkpoint(visitor)?;
+            return Ok(true)
+        }
+        if i > min(self.iterate_to_max_height,self.curr_tipset_height) {
+            let head = apply_error_policy(&self.error_policy, &self.cancel, false,
+                || MaxTipsetHeight::try_new(&api).map_err(|e| WalkError::api("chain_head", e)))?;
+            self.curr_tipset_height = match head {
+                Some(head) => head.max_height,
+                None => return Ok(false),
+            };
+            log::debug!("current largest tipset height: {}",self.curr_tipset_height);
+            if i > min(self.iterate_to_max_height,self.curr_tipset_height) {
+                match self.head_waiter.as_mut() {
+                    Some(waiter) if i <= self.iterate_to_max_height => {
+                        log::debug!("Caught up to head at height {}; waiting for new tipsets",self.curr_tipset_height);
+                        match waiter.wait_for_head_above(&api, self.curr_tipset_height, &self.cancel) {
+                            Some(new_height) => {
+                                self.curr_tipset_height = new_height;
+                                return Ok(true)
+                            },
+                            None => {
+                                log::info!("Walk cancelled while waiting for height {}",i);
+                                return Ok(false)
+                            },
+                        }
+                    },
+                    _ => return Ok(false),
+                }
             }
         }
 
-        if let Some(f) = on_finished_tipset {
-            f(i);
+        //
+        // Prefetch:  keep the heights after this one (up to the head) being fetched, and take
+        // this one's responses if they were fetched ahead.  Retries always fetch afresh.
+        //
+        let wants_blocks = visitor.wants_messages() || visitor.wants_message_cids();
+        let prefetched = match self.prefetcher.as_mut() {
+            Some(prefetcher) => {
+                prefetcher.fill(i, min(self.iterate_to_max_height,self.curr_tipset_height), wants_blocks, visitor.wants_messages());
+                prefetcher.take(i)
+            },
+            None => None,
+        };
+        let (mut prefetched_tipset, mut prefetched_blocks) = match prefetched {
+            Some(height_data) => (Some(height_data.tipset), height_data.blocks),
+            None => (None, HashMap::new()),
+        };
+
+        let tipsets = apply_error_policy(&self.error_policy, &self.cancel, false,
+            || match prefetched_tipset.take() {
+                Some(tipset_jsonval) => tipset_jsonval.map(|json_val| Tipsets::from_json(i, json_val)),
+                None => Tipsets::try_new(&api,i).map_err(|e| WalkError::api("chain_get_tipset_by_height", e)),
+            })?;
+        let tipsets = match tipsets {
+            Some(tipsets) => tipsets,
+            None => return Ok(false),
+        };
+        let tipset = tipsets.tipset();
+
+        //
+        // Reorg check:  the new tipset must be a child of the last one we visited (or the
+        // same one again, which is what ChainGetTipSetByHeight returns for a null round)
+        //
+        if let (Some(tipset), Some((_, last_key))) = (&tipset, self.visited.back()) {
+            let tipset_key = tipset.key();
+            if tipset_key != *last_key && tipset.parents() != *last_key {
+                match find_fork_point(&api, tipset, &self.visited) {
+                    Some(fork_height) => {
+                        log::info!("Reorg at height {}: chain forked after height {}",i,fork_height);
+                        while let Some((height, key)) = self.visited.pop_back() {
+                            if height <= fork_height {
+                                self.visited.push_back((height, key));
+                                break
+                            }
+                            let blocks : Vec<String> = key.cids().iter().map(|cid| cid.to_string()).collect();
+                            self.block_analyzer.revert_blocks(&blocks);
+                            visitor.on_revert_tipset(height,&blocks);
+                        }
+                        self.i = fork_height + 1;
+                        return Ok(true)
+                    },
+                    None => {
+                        log::error!("Reorg at height {} forks below the {} tipsets remembered; not reverting",i,self.visited.len());
+                    },
+                }
+            }
         }
 
         //
-        // Loop control
+        // Null round:  nothing was mined at this height, and what we got back is the tipset
+        // we visited at the height before, so report the gap instead of visiting it again
         //
-        i += 1;
-        if i > min(iterate_to_max_height,curr_tipset_height) {
-            curr_tipset_height = MaxTipsetHeight::new(&api).max_height;
-            if i > min(iterate_to_max_height,curr_tipset_height) {
-                break
+        if tipsets.is_null_round() {
+            log::info!("Height {} : null round",i);
+            visitor.on_null_round(i);
+            self.finish_height(i, tipset);
+            return Ok(true)
+        }
+
+        // The messages this tipset's blocks report as executed were included by its parent
+        let ts_strings : Vec<String> = tipsets.collect();
+        let parent = match &tipset {
+            Some(tipset) if visitor.wants_messages() => self.parent_tipset_ref(tipset)?,
+            _ => None,
+        };
+        self.block_analyzer.start_tipset(TipsetRef{ height: i, blocks: ts_strings.clone() }, parent, visitor);
+        visitor.on_tipset_start(i,&ts_strings);
+        for blk_cid in ts_strings {
+            visitor.on_block_start(&blk_cid);
+
+            log::info!("Height {} : blk_cid {}...",i,blk_cid);
+
+            // Iterate complete messages referenced in this block, and cids of new messages first
+            // appearing in this block.  A block the error policy skips reports no messages.
+            if wants_blocks {
+                let block_analyzer = &mut self.block_analyzer;
+                let mut prefetched_block = prefetched_blocks.remove(&blk_cid);
+                // (a prefetched block may lack the parent messages if the block they were
+                // prefetched with was skipped)
+                apply_error_policy(&self.error_policy, &self.cancel, true,
+                    || match prefetched_block.take() {
+                        Some(Ok(block_data)) if block_data.has_parent_messages() || !block_analyzer.wants_parent_messages(visitor)
+                            => block_analyzer.visit_block_data(&blk_cid, &block_data, visitor),
+                        Some(Err(e)) => Err(e),
+                        _ => block_analyzer.visit_messages_in_block(&blk_cid, visitor),
+                    })?;
             }
+
+            visitor.on_block_end(&blk_cid);
         }
+
+        visitor.on_tipset_end(i);
+
+        self.finish_height(i, tipset);
+        Ok(true)
+    }
+
+    // The height and blocks of `tipset`'s parent:  usually the tipset visited last, otherwise
+    // looked up (as the error policy says; None if skipped)
+    fn parent_tipset_ref(&self, tipset: &crate::types::TipSet) -> Result<Option<TipsetRef>, WalkError> {
+        let parents = tipset.parents();
+        let parent_height = match self.visited.back() {
+            Some((height, key)) if *key == parents => Some(*height),
+            _ => {
+                let api = self.api;
+                apply_error_policy(&self.error_policy, &self.cancel, true,
+                    || api.chain_get_tipset_typed(&parents).map_err(|e| WalkError::api("chain_get_tipset", e)))?
+                    .map(|parent| parent.height)
+            },
+        };
+        Ok(parent_height.map(|height| TipsetRef{
+            height,
+            blocks: parents.cids().iter().map(|cid| cid.to_string()).collect(),
+        }))
+    }
+
+    // Moves on past height `i`, once its events have all been emitted.  `tipset` is what
+    // ChainGetTipSetByHeight returned for it (for a null round, the tipset below it).
+    fn finish_height(&mut self, i: u64, tipset: Option<crate::types::TipSet>) {
+        if let Some(tipset) = tipset {
+            self.uncommitted_checkpoint = Some(Checkpoint{ height: i, tipset_key: tipset.key() });
+            let tipset_key = tipset.key();
+            if self.visited.back().map(|(_, last_key)| *last_key != tipset_key).unwrap_or(true) {
+                self.visited.push_back((tipset.height, tipset_key));
+                if self.visited.len() > REORG_HISTORY_LEN {
+                    self.visited.pop_front();
+                }
+            }
+        }
+
+        self.i = i + 1;
+    }
+}
+
+// A store whose lock was poisoned by a panicking visitor is still usable
+fn lock_checkpoint_store(store: &SharedCheckpointStore) -> std::sync::MutexGuard<'_, dyn CheckpointStore + Send + 'static> {
+    store.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn new_prefetcher(api: &crate::api::ApiClient, options: &WalkOptions) -> Option<crate::prefetch::Prefetcher> {
+    if options.prefetch > 1 {
+        Some(crate::prefetch::Prefetcher::new(api.as_async(), options.prefetch))
+    } else {
+        None
+    }
+}
+
+// Runs `attempt`, retrying it or giving up on it as `error_policy` says for the error it
+// fails with.  Ok(None) means the block is skipped (only allowed if `can_skip`).
+fn apply_error_policy<T>(error_policy: &ErrorPolicy, cancel: &CancelHandle, can_skip: bool,
+    mut attempt: impl FnMut() -> Result<T, WalkError>) -> Result<Option<T>, WalkError>
+{
+    let mut retries : u32 = 0;
+    loop {
+        let e = match attempt() {
+            Ok(result) => return Ok(Some(result)),
+            Err(e) => e,
+        };
+        match error_policy.action(e.kind()) {
+            ErrorAction::SkipBlock if can_skip => {
+                log::error!("Skipping block: {}",e);
+                return Ok(None)
+            },
+            ErrorAction::Retry{attempts, delay} if retries < attempts => {
+                retries += 1;
+                log::warn!("{}; retrying ({}/{})",e,retries,attempts);
+                if !sleep_unless_cancelled(delay, cancel) {
+                    return Err(e)
+                }
+            },
+            _ => return Err(e),
+        }
+    }
+}
+
+// How many visited tipsets to remember for unwinding reorgs (Filecoin's finality)
+const REORG_HISTORY_LEN : usize = 900;
+
+// Follows `tipset`'s parents back until reaching a tipset in `visited`, and returns that
+// tipset's height.  None if no visited tipset is an ancestor (or the lookups fail).
+fn find_fork_point(api: &api::ApiClient, tipset: &crate::types::TipSet,
+    visited: &std::collections::VecDeque<(u64, crate::types::TipSetKey)>) -> Option<u64>
+{
+    let oldest_height = visited.front().map(|(height, _)| *height)?;
+    let mut cursor = tipset.parents();
+    loop {
+        if let Some((height, _)) = visited.iter().rev().find(|(_, key)| *key == cursor) {
+            return Some(*height);
+        }
+        match api.chain_get_tipset_typed(&cursor) {
+            Ok(parent) => {
+                if parent.height <= oldest_height {
+                    return None;
+                }
+                cursor = parent.parents();
+            },
+            Err(e) => {
+                log::error!("find_fork_point: chain_get_tipset failed: {}",e);
+                return None;
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_messages_in_block() {
+        let block_hdr : jsonrpsee::common::JsonValue = serde_json::from_str(r#"{"BLSAggregate":{"Type":2,"Data":"qqo="}}"#).unwrap();
+        let block_msgs : jsonrpsee::common::JsonValue = serde_json::from_str(r#"{
+            "BlsMessages":[{"To":"t01000"}],
+            "SecpkMessages":[{"Message":{"To":"t01001"},"Signature":{"Type":1,"Data":"u7s="}}],
+            "Cids":[{"/":"bafybls"},{"/":"bafysecp"}]
+        }"#).unwrap();
+        let new_msgs = new_messages_in_block("bafyblk", &block_hdr, &block_msgs).unwrap();
+        assert_eq!(new_msgs.len(), 2);
+        assert_eq!(new_msgs[0].0, "bafybls");
+        assert!(matches!(new_msgs[0].1, MessageTypeFlag::BlsMessage(_)));
+        assert_eq!(new_msgs[1].0, "bafysecp");
+        assert!(matches!(new_msgs[1].1, MessageTypeFlag::SecpMessage(_)));
+
+        let empty : jsonrpsee::common::JsonValue = serde_json::from_str(r#"{"BlsMessages":null,"SecpkMessages":null,"Cids":null}"#).unwrap();
+        assert_eq!(new_messages_in_block("bafyblk", &block_hdr, &empty).unwrap().len(), 0);
+
+        let short : jsonrpsee::common::JsonValue = serde_json::from_str(r#"{"BlsMessages":[{}],"SecpkMessages":[],"Cids":[]}"#).unwrap();
+        match new_messages_in_block("bafyblk", &block_hdr, &short) {
+            Err(WalkError::MessageCountMismatch{cids: 0, messages: 1, ..}) => {},
+            other => panic!("expected MessageCountMismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parent_messages_with_receipts() {
+        let parent_msgs : jsonrpsee::common::JsonValue = serde_json::from_str(r#"[
+            {"Cid":{"/":"bafymsg"},"Message":{"To":"t01000"}}
+        ]"#).unwrap();
+        let receipts : jsonrpsee::common::JsonValue = serde_json::from_str(r#"[{"ExitCode":0,"Return":null,"GasUsed":10}]"#).unwrap();
+        let complete_msgs = parent_messages_with_receipts("bafyblk", &parent_msgs, &receipts).unwrap();
+        assert_eq!(complete_msgs.len(), 1);
+        assert_eq!(complete_msgs[0].0, "bafymsg");
+
+        let no_receipts = jsonrpsee::common::JsonValue::Null;
+        assert_eq!(parent_messages_with_receipts("bafyblk", &parent_msgs, &no_receipts).unwrap_err().kind(),
+            WalkErrorKind::ReceiptCountMismatch);
+
+        let no_cid : jsonrpsee::common::JsonValue = serde_json::from_str(r#"[{"Message":{"To":"t01000"}}]"#).unwrap();
+        assert_eq!(parent_messages_with_receipts("bafyblk", &no_cid, &receipts).unwrap_err().kind(),
+            WalkErrorKind::MalformedMessage);
+    }
+
+    #[test]
+    fn test_tipsets_null_round() {
+        let tipset_json : jsonrpsee::common::JsonValue = serde_json::from_str(r#"{
+            "Cids":[{"/":"bafyblk"}], "Blocks":[], "Height":10
+        }"#).unwrap();
+        let tipsets = Tipsets::from_json(10, tipset_json.clone());
+        assert_eq!(tipsets.height(), Some(10));
+        assert!(!tipsets.is_null_round());
+
+        // asked for 11, got 10 back:  nothing was mined at 11
+        let tipsets = Tipsets::from_json(11, tipset_json);
+        assert!(tipsets.is_null_round());
+        assert_eq!(tipsets.collect::<Vec<String>>(), vec!["bafyblk"]);
+
+        // a failed request isn't a null round
+        assert!(!Tipsets::from_json(11, jsonrpsee::common::JsonValue::Null).is_null_round());
+    }
+
+    #[test]
+    fn test_parent_messages_reported_once_per_tipset() {
+        let api = api::ApiClient::new("http://127.0.0.1:9/rpc/v0");
+        let mut block_analyzer = BlockAnalyzer::new(&api);
+        let block_data = |cid: &str| BlockData{
+            block_hdr: serde_json::from_str(r#"{"BLSAggregate":{"Type":2,"Data":"qqo="}}"#).unwrap(),
+            block_msgs: serde_json::from_str(&format!(r#"{{"BlsMessages":[{{}}],"SecpkMessages":[],"Cids":[{{"/":"{}"}}]}}"#, cid)).unwrap(),
+            parents: Some((
+                serde_json::from_str(r#"[{"Cid":{"/":"bafymsg1"},"Message":{"To":"t01000"}}]"#).unwrap(),
+                serde_json::from_str(r#"[{"ExitCode":0,"Return":null,"GasUsed":10}]"#).unwrap(),
+            )),
+        };
+
+        // height 10:  bafymsg1 is included by both blocks
+        let mut new_msgs : Vec<String> = Vec::new();
+        let mut on_found_new_message_cid = |msg_cid: &str| new_msgs.push(msg_cid.to_string());
+        let mut visitor = CallbackVisitor{ on_found_new_message_cid: Some(&mut on_found_new_message_cid), ..CallbackVisitor::none() };
+        block_analyzer.start_tipset(TipsetRef{ height: 10, blocks: vec!["bafyblk10a".to_string(), "bafyblk10b".to_string()] }, None, &mut visitor);
+        block_analyzer.visit_block_data("bafyblk10a", &block_data("bafymsg1"), &mut visitor).unwrap();
+        block_analyzer.visit_block_data("bafyblk10b", &block_data("bafymsg1"), &mut visitor).unwrap();
+        assert_eq!(new_msgs, vec!["bafymsg1"]);
+
+        // height 12 (after a null round) executes it; both its blocks report it as a parent message
+        let included_in = TipsetRef{ height: 10, blocks: vec!["bafyblk10a".to_string(), "bafyblk10b".to_string()] };
+        let executed_in = TipsetRef{ height: 12, blocks: vec!["bafyblk12a".to_string(), "bafyblk12b".to_string()] };
+        let mut msgs : Vec<(String, Message)> = Vec::new();
+        let mut on_found_new_message = |msg_cid: &str, msg: &Message| msgs.push((msg_cid.to_string(), msg.clone()));
+        let mut visitor = CallbackVisitor{ on_found_new_message: Some(&mut on_found_new_message), ..CallbackVisitor::none() };
+        block_analyzer.start_tipset(executed_in.clone(), Some(included_in.clone()), &mut visitor);
+        assert!(block_analyzer.wants_parent_messages(&visitor));
+        block_analyzer.visit_block_data("bafyblk12a", &block_data("bafymsg2"), &mut visitor).unwrap();
+        assert!(!block_analyzer.wants_parent_messages(&visitor));
+        block_analyzer.visit_block_data("bafyblk12b", &block_data("bafymsg3"), &mut visitor).unwrap();
+
+        assert_eq!(msgs.len(), 1);
+        let (msg_cid, msg) = &msgs[0];
+        assert_eq!(msg_cid, "bafymsg1");
+        assert_eq!(msg.included_in, Some(included_in));
+        assert_eq!(msg.included_in_block.as_deref(), Some("bafyblk10a"));
+        assert_eq!(msg.executed_in, Some(executed_in));
+
+        // a reorg drops height 12's blocks, and with them the messages they included
+        block_analyzer.revert_blocks(&["bafyblk12a".to_string(), "bafyblk12b".to_string()]);
+        assert!(block_analyzer.incomplete_msg_cache().is_empty());
+        assert_eq!(block_analyzer.incomplete_msg_cache().metrics().duplicates, 1);
+    }
+
+    #[test]
+    fn test_cids_only_visitor_reports_no_unexecuted_messages() {
+        let api = api::ApiClient::new("http://127.0.0.1:9/rpc/v0");
+        let mut block_analyzer = BlockAnalyzer::new(&api);
+        block_analyzer.set_message_cache_epochs(2);
+        // a visitor without on_found_new_message, so no receipts are fetched
+        let block_data = |cid: &str| BlockData{
+            block_hdr: serde_json::from_str(r#"{"BLSAggregate":{"Type":2,"Data":"qqo="}}"#).unwrap(),
+            block_msgs: serde_json::from_str(&format!(r#"{{"BlsMessages":[{{}}],"SecpkMessages":[],"Cids":[{{"/":"{}"}}]}}"#, cid)).unwrap(),
+            parents: None,
+        };
+
+        let mut new_msgs : Vec<String> = Vec::new();
+        let mut unexecuted_msgs : Vec<String> = Vec::new();
+        let mut on_found_new_message_cid = |msg_cid: &str| new_msgs.push(msg_cid.to_string());
+        let mut on_unexecuted_message = |msg_cid: &str, _msg: &IncompleteMessage| unexecuted_msgs.push(msg_cid.to_string());
+        let mut visitor = CallbackVisitor{
+            on_found_new_message_cid: Some(&mut on_found_new_message_cid),
+            on_unexecuted_message: Some(&mut on_unexecuted_message),
+            ..CallbackVisitor::none()
+        };
+        for height in 10..20 {
+            let blk_cid = format!("bafyblk{}", height);
+            block_analyzer.start_tipset(TipsetRef{ height, blocks: vec![blk_cid.clone()] }, None, &mut visitor);
+            block_analyzer.visit_block_data(&blk_cid, &block_data(&format!("bafymsg{}", height)), &mut visitor).unwrap();
+        }
+        drop(visitor);
+
+        assert_eq!(new_msgs.len(), 10);
+        assert!(unexecuted_msgs.is_empty(), "reported as unexecuted: {:?}", unexecuted_msgs);
+        let metrics = block_analyzer.incomplete_msg_cache().metrics();
+        assert_eq!(metrics.unexecuted, 0);
+        // only the last two heights' messages could still be matched to receipts
+        assert_eq!(metrics.unknown, 8);
+        assert_eq!(metrics.cached, 2);
+    }
+
+    #[test]
+    fn test_apply_error_policy() {
+        let mismatch = || WalkError::MessageCountMismatch{ block_cid: "bafyblk".to_string(), cids: 0, messages: 1 };
+        let cancel = CancelHandle::new();
+
+        // skipped by default, but only where skipping is allowed
+        let policy = ErrorPolicy::default();
+        assert!(matches!(apply_error_policy(&policy, &cancel, true, || Err::<(), _>(mismatch())), Ok(None)));
+        assert!(apply_error_policy(&policy, &cancel, false, || Err::<(), _>(mismatch())).is_err());
+
+        // retried, then succeeds
+        let policy = ErrorPolicy::abort_on_all()
+            .on(WalkErrorKind::MessageCountMismatch, ErrorAction::Retry{ attempts: 2, delay: std::time::Duration::from_millis(1) });
+        let mut calls = 0;
+        let result = apply_error_policy(&policy, &cancel, true, || {
+            calls += 1;
+            if calls < 3 { Err(mismatch()) } else { Ok(calls) }
+        });
+        assert!(matches!(result, Ok(Some(3))));
+
+        // retries used up
+        let mut calls = 0;
+        let result = apply_error_policy(&policy, &cancel, true, || { calls += 1; Err::<(), _>(mismatch()) });
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_message_method_names() {
+        let msg_json : jsonrpsee::common::JsonValue = serde_json::from_str(r#"{"To":"f05","Method":4}"#).unwrap();
+        let msg = MessageBuilder::new().msg_fields(&msg_json).get();
+        assert_eq!(msg.method_name.as_deref(), Some("PublishStorageDeals"));
+
+        // a miner's methods need its actor code
+        let msg_json : jsonrpsee::common::JsonValue = serde_json::from_str(r#"{"To":"f01000","Method":5}"#).unwrap();
+        let mut msg = MessageBuilder::new().msg_fields(&msg_json).get();
+        assert_eq!(msg.method_name, None);
+        let registry = MethodRegistry::new();
+        assert_eq!(msg.resolve_method_name(&registry, &ActorType::Miner.legacy_code_cid(3)), Some("SubmitWindowedPoSt"));
+        assert!(msg.to_string().contains("Method: 5 (SubmitWindowedPoSt)\n"));
+    }
+
+    #[test]
+    fn test_message_gas_fields() {
+        let msg_json : jsonrpsee::common::JsonValue = serde_json::from_str(r#"{"GasLimit":24000000,"GasFeeCap":"101310","GasPremium":"100256"}"#).unwrap();
+        let msg = MessageBuilder::new().msg_fields(&msg_json).get();
+        assert_eq!(msg.gas_fee_cap, TokenAmount::from_atto(101310));
+        assert_eq!(msg.gas_premium, TokenAmount::from_atto(100256));
+        assert_eq!(msg.gas_price, None);
+        assert!(msg.to_string().contains("Gas fee cap: 101310 attoFIL\nGas premium: 100256 attoFIL\nGas limit: 24000000\n"));
+
+        // a message from before GasFeeCap/GasPremium
+        let msg_json : jsonrpsee::common::JsonValue = serde_json::from_str(r#"{"GasLimit":2725107,"GasPrice":"1"}"#).unwrap();
+        let msg = MessageBuilder::new().msg_fields(&msg_json).get();
+        assert!(msg.gas_fee_cap.is_zero());
+        assert_eq!(msg.gas_price, Some(TokenAmount::from_atto(1)));
+        assert!(msg.to_string().contains("Gas price: 1 attoFIL\nGas limit: 2725107\n"));
+    }
+
+    #[test]
+    fn test_message_from_signed_message() {
+        let signed_json = r#"{
+            "Message": {
+                "Version": 0,
+                "To": "f05",
+                "From": "f01024",
+                "Nonce": 7,
+                "Value": "1000000000000000000",
+                "GasLimit": 24000000,
+                "GasFeeCap": "101310",
+                "GasPremium": "100256",
+                "Method": 4,
+                "Params": "gYE="
+            },
+            "Signature": { "Type": 1, "Data": "u7s=" },
+            "CID": { "/": "bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i" }
+        }"#;
+        let signed_message : SignedMessage = serde_json::from_str(signed_json).unwrap();
+        let (msg_cid, msg) = Message::from_signed_message(&signed_message);
+        assert_eq!(msg_cid.unwrap().to_string(), "bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i");
+        assert_eq!(msg.to.as_ref().and_then(|to| to.id()), Some(5));
+        assert_eq!(msg.from.as_ref().and_then(|from| from.id()), Some(1024));
+        assert_eq!(msg.nonce, 7);
+        assert_eq!(msg.value.to_string(), "1 FIL");
+        assert_eq!(msg.gas_limit, 24000000);
+        assert_eq!(msg.gas_fee_cap, TokenAmount::from_atto(101310));
+        assert_eq!(msg.gas_premium, TokenAmount::from_atto(100256));
+        assert_eq!(msg.gas_price, None);
+        assert_eq!(msg.method, "4");
+        assert_eq!(msg.method_name.as_deref(), Some("PublishStorageDeals"));
+        assert_eq!(msg.params, "gYE=");
+        assert!(matches!(msg.msg_type, MessageTypeFlag::SecpMessage(_)));
+        assert!(matches!(msg.receipt, ReceiptStatus::NoReceipt));
+
+        // and an mpool notification carrying it
+        let update_json = format!(r#"{{ "Type": 1, "Message": {} }}"#, signed_json);
+        let update : crate::types::MpoolUpdate = serde_json::from_str(&update_json).unwrap();
+        assert_eq!(update.update_type, crate::types::MpoolChange::Remove);
+
+        // a pooled BLS message still has its own signature, and an older node sends no CID
+        let mut signed_message = update.message;
+        signed_message.signature.sig_type = 2;
+        signed_message.cid = None;
+        let (msg_cid, msg) = Message::from_signed_message(&signed_message);
+        assert!(msg_cid.is_none());
+        assert!(matches!(msg.msg_type, MessageTypeFlag::BlsSignedMessage(_)));
+        assert!(serde_json::to_value(&signed_message).unwrap().get("CID").is_none());
     }
 }
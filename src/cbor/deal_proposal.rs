@@ -1,208 +1,121 @@
+use crate::address::Address;
+use crate::cid::Cid;
+use crate::error::DealProposalError;
+use crate::tokenamount::TokenAmount;
+use serde::{Deserialize, Serialize};
 use serde_cbor::value::Value;
 
-#[derive(Debug)]
+// A storage deal proposal, as decoded from PublishStorageDeals params by
+// decode_storage_deal(), or as the node's market api returns it in json (see
+// types::MarketDeal).
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DealProposal {
-    pub piece_cid: Vec<u8>,
+    #[serde(rename = "PieceCID")]
+    pub piece_cid: Cid,
+    #[serde(rename = "PieceSize")]
     pub padded_piece_size: u64,
+    #[serde(rename = "VerifiedDeal")]
     pub is_verified_deal: bool,
-    pub client_addr: Vec::<u8>,
-    pub provider_addr: Vec::<u8>,
+    #[serde(rename = "Client")]
+    pub client_addr: Address,
+    #[serde(rename = "Provider")]
+    pub provider_addr: Address,
+    #[serde(rename = "Label", default)]
     pub label: String,
+    #[serde(rename = "StartEpoch")]
     pub start_epoch: u64,
+    #[serde(rename = "EndEpoch")]
     pub end_epoch: u64,
-    pub storage_price_per_epoch: Vec::<u8>,
-    pub provider_collateral: Vec::<u8>, 
-    pub client_collateral: Vec::<u8>,
+    #[serde(rename = "StoragePricePerEpoch")]
+    pub storage_price_per_epoch: TokenAmount,
+    #[serde(rename = "ProviderCollateral")]
+    pub provider_collateral: TokenAmount,
+    #[serde(rename = "ClientCollateral")]
+    pub client_collateral: TokenAmount,
 }
 
 impl DealProposal {
     pub fn get_piece_cid_as_str(&self) -> String {
-        let alpha = base32::Alphabet::RFC4648{padding:false};
-        let mut s = base32::encode(alpha, &self.piece_cid);
-        s = s.to_ascii_lowercase();
-        println!("base32 of piece_cid='{}'",s);
-        s
+        self.piece_cid.to_string()
+    }
+
+    // What the client pays the provider over the life of the deal
+    pub fn total_storage_fee(&self) -> TokenAmount {
+        &self.storage_price_per_epoch * self.end_epoch.saturating_sub(self.start_epoch)
     }
 }
 
-pub fn decode_storage_deal(input_base64: &str) -> Option<DealProposal> {
-    let byte_vec = base64::decode(input_base64).unwrap();
+// Decodes the first deal proposal in base64 PublishStorageDeals params.  The params are
+// [[ClientDealProposal, ...]], and each ClientDealProposal is [DealProposal, signature].
+pub fn decode_storage_deal(input_base64: &str) -> Result<DealProposal, DealProposalError> {
+    let byte_vec = base64::decode(input_base64)
+        .map_err(|e| DealProposalError::InvalidBase64(e.to_string()))?;
+    let value : Value = serde_cbor::from_slice(&byte_vec)
+        .map_err(|e| DealProposalError::InvalidCbor(e.to_string()))?;
+
+    let params = as_array(&value, "params")?;
+    let deals = as_array(first(params, "deals")?, "deals")?;
+    let client_deal = as_array(first(deals, "client deal proposal")?, "client deal proposal")?;
+    let fields = as_array(first(client_deal, "deal proposal")?, "deal proposal")?;
 
-    let byte_slice: &[u8] = &byte_vec;
-    let value : Value = match serde_cbor::from_slice(byte_slice) {
-        Ok(val) => {
-            val
-        }, 
-        Err(e) => {
-            println!("decode_storage_deal: failed at serde_cbor::from_slice: {:?}",e);
-            return None;
-        }
+    let field = |index: usize, name: &'static str| -> Result<&Value, DealProposalError> {
+        fields.get(index).ok_or_else(|| DealProposalError::InvalidField{ index, name, error: "missing".to_string() })
+    };
+    let invalid = |index: usize, name: &'static str, error: String| {
+        DealProposalError::InvalidField{ index, name, error }
     };
-    //println!("{:?}", value);
 
-    let mut piece_cid : Vec<u8>;
-    let padded_piece_size : u64;
-    let is_verified_deal: bool;
-    let mut client_addr : Vec<u8>;
-    let mut provider_addr : Vec<u8>;
-    let label : String;
-    let start_epoch : u64;
-    let end_epoch : u64;
-    let mut storage_price_per_epoch : Vec<u8>;
-    let mut provider_collateral : Vec<u8>;
-    let mut client_collateral : Vec<u8>;
-    match value {
-        Value::Array(vec) => {
-            match &vec[0] {
-                Value::Array(vec) => {
-                    match &vec[0] {
-                        Value::Array(vec) => {
-                            match &vec[0] {
-                                Value::Array(vec) => {
-                                    match &vec[0] {
-                                        Value::Bytes(bytes) => {
-                                            piece_cid = vec![0; bytes.len()];
-                                            piece_cid.copy_from_slice(&bytes);
-                                        },
-                                        _ => {
-                                            println!("decode_storage_deal: failed at vec[0]");
-                                            return None;
-                                        }
-                                    };
-                                    match &vec[1] {
-                                        Value::Integer(i) => {
-                                            padded_piece_size = *i as u64;
-                                        },
-                                        _ => {
-                                            println!("decode_storage_deal: failed at vec[1]");
-                                            return None;
-                                        }
-                                    };
-                                    match &vec[2] {
-                                        Value::Bool(b) => {
-                                            is_verified_deal = *b;
-                                        },
-                                        _ => {
-                                            println!("decode_storage_deal: failed at vec[2]");
-                                            return None;
-                                        }
-                                    };
-                                    match &vec[3] {
-                                        Value::Bytes(bytes) => {
-                                            client_addr = vec![0; bytes.len()];
-                                            client_addr.copy_from_slice(&bytes);
-                                        },
-                                        _ => {
-                                            println!("decode_storage_deal: failed at vec[3]");
-                                            return None;
-                                        }
-                                    };
-                                    match &vec[4] {
-                                        Value::Bytes(bytes) => {
-                                            provider_addr = vec![0; bytes.len()];
-                                            provider_addr.copy_from_slice(&bytes);
-                                        },
-                                        _ => {
-                                            println!("decode_storage_deal: failed at vec[4]");
-                                            return None;
-                                        }
-                                    };
-                                    match &vec[5] {
-                                        Value::Text(s) => {
-                                            label = s.to_owned();
-                                        },
-                                        _ => {
-                                            println!("decode_storage_deal: failed at vec[5]");
-                                            return None;
-                                        }
-                                    };
-                                    match &vec[6] {
-                                        Value::Integer(i) => {
-                                            start_epoch = *i as u64;
-                                        },
-                                        _ => {
-                                            println!("decode_storage_deal: failed at vec[6]");
-                                            return None;
-                                        }
-                                    };
-                                    match &vec[7] {
-                                        Value::Integer(i) => {
-                                            end_epoch = *i as u64;
-                                        },
-                                        _ => {
-                                            println!("decode_storage_deal: failed at vec[7]");
-                                            return None;
-                                        }
-                                    };
-                                    match &vec[8] {
-                                        Value::Bytes(bytes) => {
-                                            storage_price_per_epoch = vec![0; bytes.len()];
-                                            storage_price_per_epoch.copy_from_slice(&bytes);
-                                        },
-                                        _ => {
-                                            println!("decode_storage_deal: failed at vec[8]");
-                                            return None;
-                                        }
-                                    };
-                                    match &vec[9] {
-                                        Value::Bytes(bytes) => {
-                                            provider_collateral = vec![0; bytes.len()];
-                                            provider_collateral.copy_from_slice(&bytes);
-                                        },
-                                        _ => {
-                                            println!("decode_storage_deal: failed at vec[9]");
-                                            return None;
-                                        }
-                                    };
-                                    match &vec[10] {
-                                        Value::Bytes(bytes) => {
-                                            client_collateral = vec![0; bytes.len()];
-                                            client_collateral.copy_from_slice(&bytes);
-                                        },
-                                        _ => {
-                                            println!("decode_storage_deal: failed at vec[10]");
-                                            return None;
-                                        }
-                                    };
-                                },
-                                _ => {
-                                    println!("decode_storage_deal: failed at 4th inner array");
-                                    return None;
-                                },
-                            }
-                        },
-                        _ => {
-                            println!("decode_storage_deal: failed at 3rd inner array");
-                            return None;
-                        },
-                    }
-                },
-                _ => {
-                    println!("decode_storage_deal: failed at 2nd inner array");
-                    return None;
-                },
-            }
+    Ok(DealProposal{
+        piece_cid: Cid::from_cbor_value(field(0, "PieceCID")?)
+            .map_err(|e| invalid(0, "PieceCID", e.to_string()))?,
+        padded_piece_size: as_u64(field(1, "PieceSize")?)
+            .map_err(|e| invalid(1, "PieceSize", e))?,
+        is_verified_deal: match field(2, "VerifiedDeal")? {
+            Value::Bool(b) => *b,
+            other => return Err(invalid(2, "VerifiedDeal", format!("expected a bool, got {:?}", other))),
         },
-        _ => {
-            println!("decode_storage_deal: failed at 1st inner array");
-            return None;
-        }
+        client_addr: as_address(field(3, "Client")?)
+            .map_err(|e| invalid(3, "Client", e))?,
+        provider_addr: as_address(field(4, "Provider")?)
+            .map_err(|e| invalid(4, "Provider", e))?,
+        label: match field(5, "Label")? {
+            Value::Text(s) => s.to_owned(),
+            other => return Err(invalid(5, "Label", format!("expected text, got {:?}", other))),
+        },
+        start_epoch: as_u64(field(6, "StartEpoch")?)
+            .map_err(|e| invalid(6, "StartEpoch", e))?,
+        end_epoch: as_u64(field(7, "EndEpoch")?)
+            .map_err(|e| invalid(7, "EndEpoch", e))?,
+        storage_price_per_epoch: TokenAmount::from_cbor_value(field(8, "StoragePricePerEpoch")?)
+            .map_err(|e| invalid(8, "StoragePricePerEpoch", e.to_string()))?,
+        provider_collateral: TokenAmount::from_cbor_value(field(9, "ProviderCollateral")?)
+            .map_err(|e| invalid(9, "ProviderCollateral", e.to_string()))?,
+        client_collateral: TokenAmount::from_cbor_value(field(10, "ClientCollateral")?)
+            .map_err(|e| invalid(10, "ClientCollateral", e.to_string()))?,
+    })
+}
+
+fn as_array<'v>(value: &'v Value, what: &str) -> Result<&'v Vec<Value>, DealProposalError> {
+    match value {
+        Value::Array(vec) => Ok(vec),
+        _ => Err(DealProposalError::UnexpectedShape(format!("{} is not an array", what))),
     }
+}
 
-    let dp = DealProposal{
-        piece_cid: piece_cid.to_owned(),
-        padded_piece_size: padded_piece_size,
-        is_verified_deal: is_verified_deal,
-        client_addr: client_addr,
-        provider_addr: provider_addr,
-        label: label,
-        start_epoch: start_epoch,
-        end_epoch: end_epoch,
-        storage_price_per_epoch: storage_price_per_epoch,
-        provider_collateral: provider_collateral,
-        client_collateral: client_collateral,
-    };
+fn first<'v>(vec: &'v [Value], what: &str) -> Result<&'v Value, DealProposalError> {
+    vec.first().ok_or_else(|| DealProposalError::UnexpectedShape(format!("no {}", what)))
+}
 
-    //println!("dp={:?}",dp);
-    Some(dp)
+fn as_u64(value: &Value) -> Result<u64, String> {
+    match value {
+        Value::Integer(i) if *i >= 0 && *i <= u64::MAX as i128 => Ok(*i as u64),
+        other => Err(format!("expected an unsigned integer, got {:?}", other)),
+    }
+}
+
+fn as_address(value: &Value) -> Result<Address, String> {
+    match value {
+        Value::Bytes(bytes) => Address::from_bytes(bytes).map_err(|e| e.to_string()),
+        other => Err(format!("expected address bytes, got {:?}", other)),
+    }
 }
@@ -12,26 +12,44 @@ mod tests {
         let input_base64 = "gYGCi9gqWCgAAYHiA5IgIM0ZDnOXrYczT9jfC/47iVtWrdzqgFiPGBD8FHO9SmI9GiAAAAD0WDEDrrTNgZqwjUdpSHUXxGAVFAbc5OFUm5JXCBf3cF1dvhB4TyaOB4DO6ODkR3pm5iRXQwCVEWAZK7gZZTxGAAkUlMYARQAfwAAAQFhhAqeOt8l8xFd6wRXsMP7+nPwIxWfVTVpjZQxc2DAdrXxSIWXPT2W9H7JYGS1eI/jCGgqmFuxfyKCGb4MRgcB+3PuJ00mPZHqli/jeBR8ug44vXmHNwb5m2QdRASRrca8xZg==";
         let dealprop = crate::cbor::deal_proposal::decode_storage_deal(input_base64).unwrap();
         //println!("dealprop={:?}",dealprop);
-        assert!(vectors_equal(&dealprop.piece_cid, &[0, 1, 129, 226, 3, 146, 32, 32, 205, 25, 14, 115, 151, 173, 135, 51, 79, 216, 223, 11, 254, 59, 137, 91, 86, 173, 220, 234, 128, 88, 143, 24, 16, 252, 20, 115, 189, 74, 98, 61]));
+        assert!(vectors_equal(&dealprop.piece_cid.to_bytes(), &[1, 129, 226, 3, 146, 32, 32, 205, 25, 14, 115, 151, 173, 135, 51, 79, 216, 223, 11, 254, 59, 137, 91, 86, 173, 220, 234, 128, 88, 143, 24, 16, 252, 20, 115, 189, 74, 98, 61]));
     }
     #[test]
     fn test_base64_vector2() {
         let input_base64 = "gYGCi9gqWCgAAYHiA5IgIIrpVS7tcUKdcS5iZhMHSdOV0Xbq9SJdESIEJNd77VsIGgACAAD0WDEDg+33osjEKbpE2cnRG+8R5E6E2zFNy6hRND4wAFBOEy6Hv8Xm8msHip3qzN8+MXvMQwCgGmAZLtQZmqREAB6EgEQAAfwAQFhhAojvOzIRjLaQCYrNjPrZNLB/5alSskFRD8jv3HQ7dK/7iSwPPbvJE49k82J+FltbYxDSA4baR0dWxaV3Y/VQkLCHFWfbCDq1Emza5YqWUGGQ06hli+B+Ax9lcD/c3IResQ==";
         let dealprop = crate::cbor::deal_proposal::decode_storage_deal(input_base64).unwrap();
         //println!("dealprop={:?}",dealprop);
-        assert!(vectors_equal(&dealprop.piece_cid, &[0, 1, 129, 226, 3, 146, 32, 32, 138, 233, 85, 46, 237, 113, 66, 157, 113, 46, 98, 102, 19, 7, 73, 211, 149, 209, 118, 234, 245, 34, 93, 17, 34, 4, 36, 215, 123, 237, 91, 8]));
+        assert!(vectors_equal(&dealprop.piece_cid.to_bytes(), &[1, 129, 226, 3, 146, 32, 32, 138, 233, 85, 46, 237, 113, 66, 157, 113, 46, 98, 102, 19, 7, 73, 211, 149, 209, 118, 234, 245, 34, 93, 17, 34, 4, 36, 215, 123, 237, 91, 8]));
     }
 
     #[test]
     fn test_get_piece_cid_as_str() {
         let input_base64 = "gYGCi9gqWCgAAYHiA5IgIBQE6FIAy641u8U9IAdzzPYlKrqQzmo4OmPRQRDy0AQFGggAAAD0VQHh5IHxtn/1yPh3sahOqrXKYJiP2EMAoBpgGT82GcMkRQAdzWUARQAH8AAAQFhCAeb43f4jnZJ1KAG/MdnFNrfZ+CZrhe6Q6WrTJAzRkVPGGSTLBv7oKuZYKtgW6YL760ghwEhK+W3uYzm4cAIGSxkB";
-        let mut dealprop = crate::cbor::deal_proposal::decode_storage_deal(input_base64).unwrap();
-        assert_eq!(dealprop.piece_cid[0], 0);
-        assert_eq!(dealprop.piece_cid[1], 1);
-        dealprop.piece_cid[0] = 0x08 as u8;
-        dealprop.piece_cid[1] = 0x0c as u8;
+        let dealprop = crate::cbor::deal_proposal::decode_storage_deal(input_base64).unwrap();
+        assert_eq!(dealprop.piece_cid.version(), 1);
+        assert_eq!(dealprop.piece_cid.codec(), crate::cid::codec::FIL_COMMITMENT_UNSEALED);
+        assert_eq!(dealprop.piece_cid.hash().code, crate::cid::multihash_code::SHA2_256_TRUNC254_PADDED);
+        assert_eq!(dealprop.client_addr.to_bytes()[0], 1);
+        assert_eq!(dealprop.storage_price_per_epoch.format(crate::tokenamount::Unit::NanoFil), "0.5 nanoFIL");
+        assert_eq!(dealprop.provider_collateral, crate::tokenamount::TokenAmount::from_atto(133169152));
+        assert!(dealprop.client_collateral.is_zero());
+        assert_eq!(dealprop.total_storage_fee().to_string(), "0.000016887 FIL");
         let s = dealprop.get_piece_cid_as_str();
-        println!("{:?}",&dealprop);
-        println!(">>>> {}",s);
+        assert!(s.starts_with("baga6ea4sea"), "{}", s);
+        assert_eq!(s.parse::<crate::cid::Cid>().unwrap(), dealprop.piece_cid);
+    }
+
+    #[test]
+    fn test_decode_storage_deal_errors() {
+        use crate::cbor::deal_proposal::decode_storage_deal;
+        use crate::error::DealProposalError;
+        assert!(matches!(decode_storage_deal("not base64!"), Err(DealProposalError::InvalidBase64(_))));
+        // 0x81: an array of one item, with the item missing
+        assert!(matches!(decode_storage_deal("gQ=="), Err(DealProposalError::InvalidCbor(_))));
+        // 0x80: an empty array
+        assert!(matches!(decode_storage_deal("gA=="), Err(DealProposalError::UnexpectedShape(_))));
+        // [[[[]]]]: a deal proposal with no fields
+        assert_eq!(decode_storage_deal("gYGBgA==").unwrap_err(),
+            DealProposalError::InvalidField{ index: 0, name: "PieceCID", error: "missing".to_string() });
     }
 }
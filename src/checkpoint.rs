@@ -0,0 +1,114 @@
+use crate::error::WalkError;
+use crate::types::TipSetKey;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// Checkpoint / CheckpointStore
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// The last height a walk fully processed, and the tipset it found there (for a null round,
+// the tipset before it).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub height: u64,
+    pub tipset_key: TipSetKey,
+}
+
+// Where a walk keeps its Checkpoint (see blockanalyzer::WalkOptions::checkpoint_store()).
+//
+// save() is called only once a tipset's on_tipset_end has returned (for walker::ChainWalker,
+// once the consumer asks for the event after TipsetEnd), and a walk resumes just above the
+// saved checkpoint, so after a crash every tipset still reaches on_tipset_end at least once.
+// A store whose save() commits in the same transaction as the visitor's own writes makes
+// that exactly once.
+pub trait CheckpointStore {
+    // None if no walk has saved a checkpoint yet
+    fn load(&mut self) -> Result<Option<Checkpoint>, WalkError>;
+
+    fn save(&mut self, checkpoint: &Checkpoint) -> Result<(), WalkError>;
+}
+
+pub type SharedCheckpointStore = std::sync::Arc<std::sync::Mutex<dyn CheckpointStore + Send>>;
+
+// Keeps the checkpoint as json in a local file.  Each save writes a temporary file next to
+// it and renames it into place, so a crash mid-save leaves the previous checkpoint intact.
+#[derive(Debug, Clone)]
+pub struct FileCheckpointStore {
+    path: PathBuf,
+}
+
+impl FileCheckpointStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> FileCheckpointStore {
+        FileCheckpointStore{ path: path.as_ref().to_path_buf() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        let mut file_name = self.path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".tmp");
+        self.path.with_file_name(file_name)
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn load(&mut self) -> Result<Option<Checkpoint>, WalkError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(json_str) => {
+                serde_json::from_str(&json_str)
+                    .map(Some)
+                    .map_err(|e| WalkError::Checkpoint(format!("{}: {}",self.path.display(),e)))
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(WalkError::Checkpoint(format!("{}: {}",self.path.display(),e))),
+        }
+    }
+
+    fn save(&mut self, checkpoint: &Checkpoint) -> Result<(), WalkError> {
+        use std::io::Write;
+
+        let temp_path = self.temp_path();
+        let write = || -> std::io::Result<()> {
+            let json_str = serde_json::to_string(checkpoint)?;
+            let mut file = std::fs::File::create(&temp_path)?;
+            file.write_all(json_str.as_bytes())?;
+            file.sync_all()?;
+            std::fs::rename(&temp_path, &self.path)
+        };
+        write().map_err(|e| WalkError::Checkpoint(format!("{}: {}",self.path.display(),e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Cid;
+
+    #[test]
+    fn test_file_checkpoint_store() {
+        let path = std::env::temp_dir().join(format!("lotus_client_rs-checkpoint-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut store = FileCheckpointStore::new(&path);
+        assert_eq!(store.load().unwrap(), None);
+
+        let checkpoint = Checkpoint{
+            height: 42,
+            tipset_key: TipSetKey(vec!["bafy2bzaceaxm23epjsmh75yvzcecsrbavlmkcxnva66bkdebdcnyw3bjrc74u".parse::<Cid>().unwrap()]),
+        };
+        store.save(&checkpoint).unwrap();
+        assert_eq!(store.load().unwrap(), Some(checkpoint.clone()));
+        assert!(!store.temp_path().exists());
+
+        let json_str = std::fs::read_to_string(&path).unwrap();
+        assert!(json_str.contains(r#""tipset_key":[{"/":"bafy2bzaceaxm23epjsmh75yvzcecsrbavlmkcxnva66bkdebdcnyw3bjrc74u"}]"#));
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(store.load().is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,306 @@
+use crate::error::CidError;
+use crate::varint;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// Multicodec / multihash codes
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// The content codecs a CID can name that show up on the Filecoin chain
+pub mod codec {
+    pub const RAW : u64 = 0x55;
+    pub const DAG_PB : u64 = 0x70;
+    pub const DAG_CBOR : u64 = 0x71;
+    // piece CIDs (CommP) and unsealed sector CIDs (CommD)
+    pub const FIL_COMMITMENT_UNSEALED : u64 = 0xf101;
+    // sealed sector CIDs (CommR)
+    pub const FIL_COMMITMENT_SEALED : u64 = 0xf102;
+
+    pub fn name(codec: u64) -> Option<&'static str> {
+        match codec {
+            RAW => Some("raw"),
+            DAG_PB => Some("dag-pb"),
+            DAG_CBOR => Some("dag-cbor"),
+            FIL_COMMITMENT_UNSEALED => Some("fil-commitment-unsealed"),
+            FIL_COMMITMENT_SEALED => Some("fil-commitment-sealed"),
+            _ => None,
+        }
+    }
+}
+
+// The hash functions a multihash can name that show up on the Filecoin chain
+pub mod multihash_code {
+    pub const IDENTITY : u64 = 0x00;
+    pub const SHA2_256 : u64 = 0x12;
+    pub const BLAKE2B_256 : u64 = 0xb220;
+    pub const SHA2_256_TRUNC254_PADDED : u64 = 0x1012;
+    pub const POSEIDON_BLS12_381_A1_FC1 : u64 = 0xb401;
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// Multihash
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Multihash {
+    // one of multihash_code
+    pub code: u64,
+    pub digest: Vec<u8>,
+}
+
+impl Multihash {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = varint::encode(self.code);
+        bytes.extend(varint::encode(self.digest.len() as u64));
+        bytes.extend_from_slice(&self.digest);
+        bytes
+    }
+
+    // The multihash `bytes` starts with, and how many bytes it took
+    fn read(bytes: &[u8]) -> Result<(Multihash, usize), CidError> {
+        let malformed = || CidError::Malformed("bad multihash".to_string());
+        let (code, code_len) = varint::decode(bytes).ok_or_else(malformed)?;
+        let (digest_len, len_len) = varint::decode(&bytes[code_len..]).ok_or_else(malformed)?;
+        let start = code_len + len_len;
+        let end = start.checked_add(digest_len as usize).filter(|end| *end <= bytes.len()).ok_or_else(malformed)?;
+        Ok((Multihash{ code, digest: bytes[start..end].to_vec() }, end))
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// Cid
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// CBOR tag for a CID link
+const CBOR_TAG_CID : u64 = 42;
+
+// A content identifier.  Parses from and prints to its string form (bafy..., Qm...), and
+// converts to and from the binary form with from_bytes()/to_bytes() and the CBOR tag 42
+// form with from_cbor_value()/to_cbor_value().
+//
+// In json (as Lotus sends it) a Cid is `{"/": "bafy..."}`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Cid {
+    version: u64,
+    codec: u64,
+    hash: Multihash,
+}
+
+impl Cid {
+    pub fn new_v1(codec: u64, hash: Multihash) -> Cid {
+        Cid{ version: 1, codec, hash }
+    }
+
+    // A CIDv0 is always a dag-pb sha2-256 link
+    pub fn new_v0(hash: Multihash) -> Result<Cid, CidError> {
+        if hash.code != multihash_code::SHA2_256 || hash.digest.len() != 32 {
+            return Err(CidError::Malformed("a CIDv0 must be a 32 byte sha2-256 multihash".to_string()));
+        }
+        Ok(Cid{ version: 0, codec: codec::DAG_PB, hash })
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    // One of the `codec` constants (see codec::name())
+    pub fn codec(&self) -> u64 {
+        self.codec
+    }
+
+    pub fn hash(&self) -> &Multihash {
+        &self.hash
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Cid, CidError> {
+        // a CIDv0 is a bare sha2-256 multihash
+        if bytes.len() == 34 && bytes[0] == 0x12 && bytes[1] == 0x20 {
+            let (hash, _) = Multihash::read(bytes)?;
+            return Cid::new_v0(hash);
+        }
+        let malformed = |what: &str| CidError::Malformed(what.to_string());
+        let (version, version_len) = varint::decode(bytes).ok_or_else(|| malformed("bad version"))?;
+        if version != 1 {
+            return Err(CidError::UnknownVersion(version));
+        }
+        let (codec, codec_len) = varint::decode(&bytes[version_len..]).ok_or_else(|| malformed("bad codec"))?;
+        let (hash, hash_len) = Multihash::read(&bytes[version_len + codec_len..])?;
+        if version_len + codec_len + hash_len != bytes.len() {
+            return Err(malformed("trailing bytes"));
+        }
+        Ok(Cid::new_v1(codec, hash))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if self.version == 0 {
+            return self.hash.to_bytes();
+        }
+        let mut bytes = varint::encode(self.version);
+        bytes.extend(varint::encode(self.codec));
+        bytes.extend(self.hash.to_bytes());
+        bytes
+    }
+
+    // From a CBOR link:  tag 42 around the binary form with a 0x00 (identity multibase)
+    // byte in front.  A bare byte string is accepted too, as serde_cbor hands those out
+    // when decoding without tags.
+    pub fn from_cbor_value(value: &serde_cbor::Value) -> Result<Cid, CidError> {
+        let bytes = match value {
+            serde_cbor::Value::Tag(CBOR_TAG_CID, inner) => match inner.as_ref() {
+                serde_cbor::Value::Bytes(bytes) => bytes,
+                _ => return Err(CidError::Malformed("tag 42 around something other than bytes".to_string())),
+            },
+            serde_cbor::Value::Bytes(bytes) => bytes,
+            _ => return Err(CidError::Malformed("not a CBOR link".to_string())),
+        };
+        match bytes.split_first() {
+            Some((0x00, cid_bytes)) => Cid::from_bytes(cid_bytes),
+            _ => Err(CidError::Multibase("CBOR link without its 0x00 prefix".to_string())),
+        }
+    }
+
+    pub fn to_cbor_value(&self) -> serde_cbor::Value {
+        let mut bytes = vec![0x00];
+        bytes.extend(self.to_bytes());
+        serde_cbor::Value::Tag(CBOR_TAG_CID, Box::new(serde_cbor::Value::Bytes(bytes)))
+    }
+}
+
+impl std::fmt::Display for Cid {
+    // base58btc for a CIDv0, multibase base32 (b...) for a CIDv1, as Lotus prints them
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.version == 0 {
+            write!(f, "{}", base58_encode(&self.to_bytes()))
+        } else {
+            let encoded = base32::encode(base32::Alphabet::RFC4648{ padding: false }, &self.to_bytes());
+            write!(f, "b{}", encoded.to_ascii_lowercase())
+        }
+    }
+}
+
+impl std::str::FromStr for Cid {
+    type Err = CidError;
+
+    fn from_str(s: &str) -> Result<Cid, CidError> {
+        let bad_multibase = || CidError::Multibase(s.to_string());
+        if s.len() == 46 && s.starts_with("Qm") {
+            return Cid::from_bytes(&base58_decode(s).ok_or_else(bad_multibase)?);
+        }
+        let mut chars = s.chars();
+        let bytes = match chars.next() {
+            Some('b') if chars.as_str().chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()) => {
+                base32::decode(base32::Alphabet::RFC4648{ padding: false }, &chars.as_str().to_ascii_uppercase())
+            },
+            Some('B') if chars.as_str().chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) => {
+                base32::decode(base32::Alphabet::RFC4648{ padding: false }, chars.as_str())
+            },
+            Some('z') => base58_decode(chars.as_str()),
+            _ => None,
+        };
+        Cid::from_bytes(&bytes.ok_or_else(bad_multibase)?)
+    }
+}
+
+impl Serialize for Cid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CidJson{ cid: self.to_string() }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Cid, D::Error> {
+        let cid_json = CidJson::deserialize(deserializer)?;
+        cid_json.cid.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CidJson {
+    #[serde(rename = "/")]
+    cid: String,
+}
+
+const BASE58_ALPHABET : &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+fn base58_encode(bytes: &[u8]) -> String {
+    // base 58 digits, least significant first
+    let mut digits : Vec<u8> = Vec::new();
+    for byte in bytes {
+        let mut carry = *byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let leading_zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+    std::iter::repeat('1').take(leading_zeros)
+        .chain(digits.iter().rev().map(|digit| BASE58_ALPHABET[*digit as usize] as char))
+        .collect()
+}
+
+fn base58_decode(s: &str) -> Option<Vec<u8>> {
+    // bytes, least significant first
+    let mut bytes : Vec<u8> = Vec::new();
+    for c in s.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|a| *a == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push((carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+    let leading_ones = s.bytes().take_while(|c| *c == b'1').count();
+    Some(std::iter::repeat(0).take(leading_ones).chain(bytes.into_iter().rev()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cid_v1() {
+        let s = "bafy2bzacebazmwzz7v5svaqwxhuk26jtco526k2vlzb5dt4ii6cuoszmq5hx4";
+        let cid : Cid = s.parse().unwrap();
+        assert_eq!(cid.version(), 1);
+        assert_eq!(codec::name(cid.codec()), Some("dag-cbor"));
+        assert_eq!(cid.hash().code, multihash_code::BLAKE2B_256);
+        assert_eq!(cid.hash().digest.len(), 32);
+        assert_eq!(cid.to_string(), s);
+        assert_eq!(Cid::from_bytes(&cid.to_bytes()).unwrap(), cid);
+        assert_eq!(Cid::from_cbor_value(&cid.to_cbor_value()).unwrap(), cid);
+        assert_eq!(s.to_uppercase().parse::<Cid>().unwrap(), cid);
+
+        let json = serde_json::to_string(&cid).unwrap();
+        assert_eq!(json, format!(r#"{{"/":"{}"}}"#, s));
+        assert_eq!(serde_json::from_str::<Cid>(&json).unwrap(), cid);
+
+        assert!("bafy2bzacea".parse::<Cid>().is_err());
+        assert!("xafy2bzacebazmwzz7v5svaqwxhuk26jtco526k2vlzb5dt4ii6cuoszmq5hx4".parse::<Cid>().is_err());
+    }
+
+    #[test]
+    fn test_cid_v0() {
+        let s = "QmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1n";
+        let cid : Cid = s.parse().unwrap();
+        assert_eq!(cid.version(), 0);
+        assert_eq!(cid.codec(), codec::DAG_PB);
+        assert_eq!(cid.hash().code, multihash_code::SHA2_256);
+        assert_eq!(cid.to_string(), s);
+        assert_eq!(format!("z{}", s).parse::<Cid>().unwrap(), cid, "the same bytes with a multibase prefix");
+    }
+}
@@ -0,0 +1,306 @@
+use std::fmt;
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// LotusError - returned by every api::ApiClient call
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone)]
+pub enum LotusError {
+    // The request never got a JSON-RPC answer back:  node down, connection refused,
+    // bad endpoint url, rejected auth token (HTTP 401), etc.
+    Transport(String),
+
+    // The node answered with a JSON-RPC error object, e.g. "block not found"
+    Rpc {
+        code: i64,
+        message: String,
+        data: Option<jsonrpsee::common::JsonValue>,
+    },
+
+    // The node answered but the result could not be parsed into the expected type
+    Deserialize(String),
+
+    // No answer within the client's configured timeout
+    Timeout(std::time::Duration),
+
+    // The client could not be configured, e.g. a malformed FULLNODE_API_INFO or an
+    // unreadable Lotus repo token/config.toml
+    Config(String),
+}
+
+impl LotusError {
+    pub fn is_transport(&self) -> bool {
+        matches!(self, LotusError::Transport(_))
+    }
+
+    pub fn is_rpc(&self) -> bool {
+        matches!(self, LotusError::Rpc{..})
+    }
+
+    pub fn is_deserialize(&self) -> bool {
+        matches!(self, LotusError::Deserialize(_))
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, LotusError::Timeout(_))
+    }
+
+    pub fn is_config(&self) -> bool {
+        matches!(self, LotusError::Config(_))
+    }
+}
+
+impl fmt::Display for LotusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LotusError::Transport(s) => write!(f, "transport error: {}", s),
+            LotusError::Rpc{code, message, data: Some(data)} => write!(f, "rpc error {}: {} ({})", code, message, data),
+            LotusError::Rpc{code, message, data: None} => write!(f, "rpc error {}: {}", code, message),
+            LotusError::Deserialize(s) => write!(f, "failed to deserialize response: {}", s),
+            LotusError::Timeout(d) => write!(f, "request timed out after {:?}", d),
+            LotusError::Config(s) => write!(f, "configuration error: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for LotusError {}
+
+impl<E: fmt::Display> From<jsonrpsee::raw::RawClientError<E>> for LotusError {
+    fn from(e: jsonrpsee::raw::RawClientError<E>) -> LotusError {
+        match e {
+            jsonrpsee::raw::RawClientError::RequestError(rpc_err) => {
+                LotusError::Rpc{
+                    code: rpc_err.code.code(),
+                    message: rpc_err.message,
+                    data: rpc_err.data,
+                }
+            },
+            other => LotusError::Transport(other.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for LotusError {
+    fn from(e: serde_json::Error) -> LotusError {
+        LotusError::Deserialize(e.to_string())
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// WalkError - why a blockanalyzer walk could not process a tipset or block
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone)]
+pub enum WalkError {
+    // An api call the walk depends on failed; `what` names the call
+    Api {
+        what: String,
+        error: LotusError,
+    },
+
+    // Filecoin.ChainGetBlockMessages listed a different number of CIDs than it returned
+    // BLS plus secp messages
+    MessageCountMismatch {
+        block_cid: String,
+        cids: usize,
+        messages: usize,
+    },
+
+    // Filecoin.ChainGetParentMessages and .ChainGetParentReceipts returned different
+    // numbers of entries
+    ReceiptCountMismatch {
+        block_cid: String,
+        messages: usize,
+        receipts: usize,
+    },
+
+    // A message or CID entry was missing fields the walk needs; `json` is the entry
+    MalformedMessage {
+        block_cid: String,
+        json: String,
+    },
+
+    // A checkpoint::CheckpointStore could not load or save the walk's checkpoint
+    Checkpoint(String),
+}
+
+// The variants of WalkError without their data, for blockanalyzer::ErrorPolicy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WalkErrorKind {
+    Api,
+    MessageCountMismatch,
+    ReceiptCountMismatch,
+    MalformedMessage,
+    Checkpoint,
+}
+
+impl WalkError {
+    pub fn api(what: &str, error: LotusError) -> WalkError {
+        WalkError::Api{ what: what.to_string(), error }
+    }
+
+    pub fn kind(&self) -> WalkErrorKind {
+        match self {
+            WalkError::Api{..} => WalkErrorKind::Api,
+            WalkError::MessageCountMismatch{..} => WalkErrorKind::MessageCountMismatch,
+            WalkError::ReceiptCountMismatch{..} => WalkErrorKind::ReceiptCountMismatch,
+            WalkError::MalformedMessage{..} => WalkErrorKind::MalformedMessage,
+            WalkError::Checkpoint(_) => WalkErrorKind::Checkpoint,
+        }
+    }
+}
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WalkError::Api{what, error} => write!(f, "{} failed: {}", what, error),
+            WalkError::MessageCountMismatch{block_cid, cids, messages} =>
+                write!(f, "block {}: {} message CIDs but {} BLS+secp messages", block_cid, cids, messages),
+            WalkError::ReceiptCountMismatch{block_cid, messages, receipts} =>
+                write!(f, "block {}: {} parent messages but {} parent receipts", block_cid, messages, receipts),
+            WalkError::MalformedMessage{block_cid, json} =>
+                write!(f, "block {}: malformed message entry: {}", block_cid, json),
+            WalkError::Checkpoint(s) => write!(f, "checkpoint error: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for WalkError {}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// AddressError - why a string or byte form couldn't be parsed as an address::Address
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    // The first character of the string form wasn't a known network prefix (f or t)
+    UnknownNetwork(String),
+
+    // The protocol (the second character of the string form, or the first byte of the
+    // byte form) wasn't one of 0-4
+    UnknownProtocol(String),
+
+    // The payload had the wrong length or wasn't valid for its protocol
+    InvalidPayload(String),
+
+    // The string form's checksum didn't match its payload
+    InvalidChecksum(String),
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddressError::UnknownNetwork(s) => write!(f, "unknown network prefix: {}", s),
+            AddressError::UnknownProtocol(s) => write!(f, "unknown address protocol: {}", s),
+            AddressError::InvalidPayload(s) => write!(f, "invalid address payload: {}", s),
+            AddressError::InvalidChecksum(s) => write!(f, "invalid address checksum: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// CidError - why a string, byte or CBOR form couldn't be parsed as a cid::Cid
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CidError {
+    // The string form's multibase prefix isn't one we decode, or its characters aren't
+    // valid in that base
+    Multibase(String),
+
+    // A version other than 0 or 1
+    UnknownVersion(u64),
+
+    // The binary form was truncated, had trailing bytes, or had a bad varint
+    Malformed(String),
+}
+
+impl fmt::Display for CidError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CidError::Multibase(s) => write!(f, "invalid multibase string: {}", s),
+            CidError::UnknownVersion(v) => write!(f, "unknown cid version: {}", v),
+            CidError::Malformed(s) => write!(f, "malformed cid: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for CidError {}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// TokenAmountError - why a string or byte form couldn't be parsed as a
+// tokenamount::TokenAmount
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenAmountError {
+    // Not a decimal number, an unknown unit, or more decimal places than the unit allows
+    InvalidString(String),
+
+    // The CBOR bigint byte form had a sign byte other than 0 or 1
+    InvalidBytes(String),
+}
+
+impl fmt::Display for TokenAmountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenAmountError::InvalidString(s) => write!(f, "invalid token amount: {}", s),
+            TokenAmountError::InvalidBytes(s) => write!(f, "invalid token amount bytes: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for TokenAmountError {}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// DealProposalError - why cbor::deal_proposal::decode_storage_deal couldn't decode a
+// PublishStorageDeals params blob
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DealProposalError {
+    // The input wasn't valid base64
+    InvalidBase64(String),
+
+    // The decoded bytes weren't valid CBOR
+    InvalidCbor(String),
+
+    // The CBOR wasn't nested the way PublishStorageDeals params are; the string names the
+    // part that was missing or wasn't an array
+    UnexpectedShape(String),
+
+    // A field of the proposal was missing, had the wrong CBOR type, or held an invalid value
+    InvalidField {
+        index: usize,
+        name: &'static str,
+        error: String,
+    },
+}
+
+impl fmt::Display for DealProposalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DealProposalError::InvalidBase64(s) => write!(f, "invalid base64: {}", s),
+            DealProposalError::InvalidCbor(s) => write!(f, "invalid cbor: {}", s),
+            DealProposalError::UnexpectedShape(s) => write!(f, "unexpected deal params shape: {}", s),
+            DealProposalError::InvalidField{index, name, error} =>
+                write!(f, "deal proposal field {} ({}): {}", index, name, error),
+        }
+    }
+}
+
+impl std::error::Error for DealProposalError {}
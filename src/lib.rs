@@ -1,4 +1,17 @@
 pub mod api;
+pub mod error;
+pub mod ws;
 pub mod blockanalyzer;
+pub mod visitor;
+pub mod walker;
+mod prefetch;
+pub mod checkpoint;
+pub mod msgcache;
+pub mod address;
+pub mod cid;
+pub mod tokenamount;
+pub mod actors;
+mod varint;
 #[macro_use] mod macros;
 pub mod cbor;
+pub mod types;
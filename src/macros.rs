@@ -39,10 +39,10 @@ macro_rules! json_val_to_string {
         let mut $receiving_variable: String = $receiving_variable_default_value.to_string();
         let json_path = format!($json_path);
         if let Some(jsonval) = $jsonval.pointer(&json_path) {
-            $receiving_variable = jsonval.to_string();
-            if $receiving_variable.len()>2 {
-                $receiving_variable = $receiving_variable[1..$receiving_variable.len()-1].to_string();
-            }
+            $receiving_variable = match jsonval.as_str() {
+                Some(inner_val) => inner_val.to_string(),
+                None => jsonval.to_string(),
+            };
         }
     }
 }
@@ -54,10 +54,10 @@ macro_rules! json_val_to_string_with_formatter {
         let mut $receiving_variable: String = $receiving_variable_default_value.to_string();
         let json_path = format!($json_path, $arg0);
         if let Some(jsonval) = $jsonval.pointer(&json_path) {
-            $receiving_variable = jsonval.to_string();
-            if $receiving_variable.len()>2 {
-                $receiving_variable = $receiving_variable[1..$receiving_variable.len()-1].to_string();
-            }
+            $receiving_variable = match jsonval.as_str() {
+                Some(inner_val) => inner_val.to_string(),
+                None => jsonval.to_string(),
+            };
         }
     }
 }
@@ -0,0 +1,233 @@
+use crate::blockanalyzer::MessageTypeFlag;
+use std::collections::{BTreeMap, HashMap};
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// IncompleteMessageCache - messages seen included but not yet executed
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// How many epochs a message may wait for its receipt by default.  A message is executed by
+// the next tipset mined on top of the one including it, so anything much older than the
+// longest run of null rounds never will be.
+pub const DEFAULT_MESSAGE_CACHE_EPOCHS : u64 = 10;
+
+// What BlockAnalyzer remembers of a message between seeing it included in a block and
+// seeing it executed
+#[derive(Debug, Clone)]
+pub struct IncompleteMessage {
+    pub msg_type: MessageTypeFlag,
+    // the first block seen to include it, and its height
+    pub block_cid: String,
+    pub height: u64,
+}
+
+// Counters for an IncompleteMessageCache, since it was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageCacheMetrics {
+    // messages in the cache now, and the most there have been at once
+    pub cached: usize,
+    pub peak: usize,
+    pub inserted: u64,
+    // included again (by another block) while already cached
+    pub duplicates: u64,
+    // removed on finding their receipt
+    pub executed: u64,
+    // removed because a reorg dropped the block including them
+    pub reverted: u64,
+    // evicted without ever finding their receipt
+    pub unexecuted: u64,
+    // forgotten because the receipts that would have shown whether they executed weren't
+    // looked at (the visitor didn't want messages, or the error policy skipped them)
+    pub unknown: u64,
+}
+
+// The messages a walk has seen included in a block but not yet executed, by msg cid.
+// Entries older than `max_epochs` are evicted by evict_older_than(), which returns them so
+// they can be reported as never executed.
+#[derive(Debug, Clone)]
+pub struct IncompleteMessageCache {
+    max_epochs:     u64,
+    msgs:           HashMap<String, IncompleteMessage>,
+    // The msg cids in `msgs` by the height that included them, for eviction
+    by_height:      BTreeMap<u64, Vec<String>>,
+    metrics:        MessageCacheMetrics,
+}
+
+impl Default for IncompleteMessageCache {
+    fn default() -> IncompleteMessageCache {
+        IncompleteMessageCache::new(DEFAULT_MESSAGE_CACHE_EPOCHS)
+    }
+}
+
+impl IncompleteMessageCache {
+    pub fn new(max_epochs: u64) -> IncompleteMessageCache {
+        IncompleteMessageCache{
+            max_epochs,
+            msgs: HashMap::new(),
+            by_height: BTreeMap::new(),
+            metrics: MessageCacheMetrics::default(),
+        }
+    }
+
+    pub fn max_epochs(&self) -> u64 {
+        self.max_epochs
+    }
+
+    pub fn set_max_epochs(&mut self, max_epochs: u64) {
+        self.max_epochs = max_epochs;
+    }
+
+    pub fn len(&self) -> usize {
+        self.msgs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.msgs.is_empty()
+    }
+
+    pub fn contains(&self, msg_cid: &str) -> bool {
+        self.msgs.contains_key(msg_cid)
+    }
+
+    pub fn get(&self, msg_cid: &str) -> Option<&IncompleteMessage> {
+        self.msgs.get(msg_cid)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &IncompleteMessage)> {
+        self.msgs.iter()
+    }
+
+    pub fn metrics(&self) -> MessageCacheMetrics {
+        self.metrics
+    }
+
+    // Adds a message first seen included.  Returns false (and keeps the first block) if it
+    // is already cached.
+    pub fn insert(&mut self, msg_cid: String, msg: IncompleteMessage) -> bool {
+        if self.msgs.contains_key(&msg_cid) {
+            self.metrics.duplicates += 1;
+            return false
+        }
+        self.by_height.entry(msg.height).or_insert_with(Vec::new).push(msg_cid.clone());
+        self.msgs.insert(msg_cid, msg);
+        self.metrics.inserted += 1;
+        self.update_len();
+        true
+    }
+
+    // Removes a message on finding its receipt
+    pub fn remove(&mut self, msg_cid: &str) -> Option<IncompleteMessage> {
+        let msg = self.take(msg_cid)?;
+        self.metrics.executed += 1;
+        Some(msg)
+    }
+
+    // Removes the messages first seen in `blocks`, which a reorg dropped, and returns how
+    // many there were
+    pub fn remove_blocks(&mut self, blocks: &[String]) -> usize {
+        let msg_cids : Vec<String> = self.msgs.iter()
+            .filter(|(_, msg)| blocks.contains(&msg.block_cid))
+            .map(|(msg_cid, _)| msg_cid.clone())
+            .collect();
+        for msg_cid in &msg_cids {
+            self.take(msg_cid);
+        }
+        self.metrics.reverted += msg_cids.len() as u64;
+        msg_cids.len()
+    }
+
+    // Evicts and returns the messages included more than `max_epochs` below `height`, which
+    // will never be executed, oldest first
+    pub fn evict_older_than(&mut self, height: u64) -> Vec<(String, IncompleteMessage)> {
+        let oldest_kept = height.saturating_sub(self.max_epochs);
+        let kept = self.by_height.split_off(&oldest_kept);
+        let evicted_heights = std::mem::replace(&mut self.by_height, kept);
+        let mut evicted = Vec::new();
+        for (_, msg_cids) in evicted_heights {
+            for msg_cid in msg_cids {
+                if let Some(msg) = self.msgs.remove(&msg_cid) {
+                    evicted.push((msg_cid, msg));
+                }
+            }
+        }
+        self.metrics.unexecuted += evicted.len() as u64;
+        self.update_len();
+        evicted
+    }
+
+    // Forgets the messages included below `height` without reporting them, when the receipts
+    // of a tipset that may have executed them weren't looked at, and returns how many there
+    // were
+    pub fn forget_older_than(&mut self, height: u64) -> usize {
+        let kept = self.by_height.split_off(&height);
+        let forgotten_heights = std::mem::replace(&mut self.by_height, kept);
+        let mut forgotten = 0;
+        for (_, msg_cids) in forgotten_heights {
+            for msg_cid in msg_cids {
+                if self.msgs.remove(&msg_cid).is_some() {
+                    forgotten += 1;
+                }
+            }
+        }
+        self.metrics.unknown += forgotten as u64;
+        self.update_len();
+        forgotten
+    }
+
+    fn take(&mut self, msg_cid: &str) -> Option<IncompleteMessage> {
+        let msg = self.msgs.remove(msg_cid)?;
+        if let Some(msg_cids) = self.by_height.get_mut(&msg.height) {
+            msg_cids.retain(|cid| cid != msg_cid);
+            if msg_cids.is_empty() {
+                self.by_height.remove(&msg.height);
+            }
+        }
+        self.update_len();
+        Some(msg)
+    }
+
+    fn update_len(&mut self) {
+        self.metrics.cached = self.msgs.len();
+        self.metrics.peak = self.metrics.peak.max(self.msgs.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn incomplete_msg(block_cid: &str, height: u64) -> IncompleteMessage {
+        IncompleteMessage{ msg_type: MessageTypeFlag::Unknown, block_cid: block_cid.to_string(), height }
+    }
+
+    #[test]
+    fn test_incomplete_message_cache() {
+        let mut cache = IncompleteMessageCache::new(2);
+        assert!(cache.insert("bafymsg1".to_string(), incomplete_msg("bafyblk10", 10)));
+        assert!(!cache.insert("bafymsg1".to_string(), incomplete_msg("bafyblk10b", 10)), "first block wins");
+        assert!(cache.insert("bafymsg2".to_string(), incomplete_msg("bafyblk10", 10)));
+        assert!(cache.insert("bafymsg3".to_string(), incomplete_msg("bafyblk11", 11)));
+        assert!(cache.insert("bafymsg4".to_string(), incomplete_msg("bafyblk12", 12)));
+        assert_eq!(cache.get("bafymsg1").unwrap().block_cid, "bafyblk10");
+
+        assert!(cache.remove("bafymsg2").is_some());
+        assert!(cache.remove("bafymsg2").is_none());
+        assert_eq!(cache.remove_blocks(&["bafyblk12".to_string()]), 1);
+
+        // at height 13, only what height 10 included is more than 2 epochs old
+        assert!(cache.evict_older_than(12).is_empty());
+        let evicted = cache.evict_older_than(13);
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].0, "bafymsg1");
+        assert!(cache.contains("bafymsg3"));
+
+        assert!(cache.insert("bafymsg5".to_string(), incomplete_msg("bafyblk13", 13)));
+        assert_eq!(cache.forget_older_than(13), 1);
+        assert!(!cache.contains("bafymsg3") && cache.contains("bafymsg5"));
+
+        assert_eq!(cache.metrics(), MessageCacheMetrics{
+            cached: 1, peak: 4, inserted: 5, duplicates: 1, executed: 1, reverted: 1, unexecuted: 1, unknown: 1,
+        });
+    }
+}
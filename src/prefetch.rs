@@ -0,0 +1,126 @@
+use crate::api::AsyncApiClient;
+use crate::blockanalyzer::{BlockData, Tipsets};
+use crate::error::WalkError;
+use std::collections::{HashMap, VecDeque};
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// Prefetcher - fetches the heights ahead of a walk in parallel
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// A tipset and its blocks' api responses, fetched before the walk reached its height
+pub(crate) struct HeightData {
+    pub tipset:     Result<jsonrpsee::common::JsonValue, WalkError>,
+    // by block cid; empty if the tipset couldn't be fetched
+    pub blocks:     HashMap<String, Result<BlockData, WalkError>>,
+}
+
+impl HeightData {
+    async fn fetch(api: AsyncApiClient, height: u64, wants_blocks: bool, wants_messages: bool) -> HeightData {
+        let tipset = api.chain_get_tipset_by_height(height).await
+            .map_err(|e| WalkError::api("chain_get_tipset_by_height", e));
+        let mut blocks = HashMap::new();
+        if let (Ok(tipset_jsonval), true) = (&tipset, wants_blocks) {
+            // a null round's blocks are those of the height before it, which the walk skips
+            let tipsets = Tipsets::from_json(height, tipset_jsonval.clone());
+            let blk_cids : Vec<String> = if tipsets.is_null_round() { Vec::new() } else { tipsets.collect() };
+            for blk_cid in blk_cids {
+                // the blocks of a tipset share their parent messages, so one block's will do
+                let with_parent_messages = wants_messages
+                    && !blocks.values().any(|block_data: &Result<BlockData, WalkError>| block_data.as_ref().map(BlockData::has_parent_messages).unwrap_or(false));
+                let block_data = BlockData::fetch(&api, &blk_cid, with_parent_messages).await;
+                blocks.insert(blk_cid, block_data);
+            }
+        }
+        HeightData{ tipset, blocks }
+    }
+}
+
+// Keeps up to one fetch per connection running on the async-std executor, for consecutive
+// heights starting at the one the walk will visit next.  The fetches run in parallel but
+// take() hands them out strictly in height order.
+pub(crate) struct Prefetcher {
+    connections:        Vec<AsyncApiClient>,
+    next_connection:    usize,
+    pending:            VecDeque<(u64, async_std::task::JoinHandle<HeightData>)>,
+}
+
+impl Prefetcher {
+    // `depth` heights are fetched ahead, each over its own connection
+    pub(crate) fn new(api: &AsyncApiClient, depth: usize) -> Prefetcher {
+        Prefetcher{
+            connections: (0..depth.max(1)).map(|_| api.new_connection()).collect(),
+            next_connection: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    // Starts fetching the heights from `next_height` up to `last_height` that aren't being
+    // fetched yet, as far ahead as the depth allows.  If the walk moved somewhere other than
+    // the next queued height (after a reorg), everything queued is cancelled first, so the new
+    // fetches don't share the connections with work that would be thrown away.
+    pub(crate) fn fill(&mut self, next_height: u64, last_height: u64, wants_blocks: bool, wants_messages: bool) {
+        if self.pending.front().map(|(height, _)| *height != next_height).unwrap_or(false) {
+            log::debug!("Prefetcher: walk moved to height {}; cancelling {} prefetched heights",next_height,self.pending.len());
+            // dropping a JoinHandle would only detach its fetch
+            let stale : Vec<async_std::task::JoinHandle<HeightData>> = self.pending.drain(..).map(|(_, handle)| handle).collect();
+            async_std::task::block_on(async {
+                for handle in stale {
+                    handle.cancel().await;
+                }
+            });
+        }
+        let mut height = match self.pending.back() {
+            Some((height, _)) => height + 1,
+            None => next_height,
+        };
+        while height <= last_height && self.pending.len() < self.connections.len() {
+            let api = self.connections[self.next_connection].clone();
+            self.next_connection = (self.next_connection + 1) % self.connections.len();
+            let handle = async_std::task::spawn(HeightData::fetch(api, height, wants_blocks, wants_messages));
+            self.pending.push_back((height, handle));
+            height += 1;
+        }
+    }
+
+    // Waits for and returns the data for `height`, if it is the next height queued
+    pub(crate) fn take(&mut self, height: u64) -> Option<HeightData> {
+        match self.pending.front() {
+            Some((front_height, _)) if *front_height == height => {
+                let (_, handle) = self.pending.pop_front()?;
+                Some(async_std::task::block_on(handle))
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending_heights(prefetcher: &Prefetcher) -> Vec<u64> {
+        prefetcher.pending.iter().map(|(height, _)| *height).collect()
+    }
+
+    #[test]
+    fn test_prefetcher_queue() {
+        // nothing listens here, so every fetch fails fast; only the queueing matters
+        let api = AsyncApiClient::new("http://127.0.0.1:9/rpc/v0");
+        let mut prefetcher = Prefetcher::new(&api, 3);
+
+        prefetcher.fill(10, 11, false, false);
+        assert_eq!(pending_heights(&prefetcher), vec![10, 11], "never past the last height");
+        prefetcher.fill(10, 100, false, false);
+        assert_eq!(pending_heights(&prefetcher), vec![10, 11, 12]);
+
+        assert!(prefetcher.take(11).is_none(), "only the next height is handed out");
+        assert!(prefetcher.take(10).is_some());
+        assert_eq!(pending_heights(&prefetcher), vec![11, 12]);
+
+        // the walk jumped back (a reorg), so the queue starts over
+        prefetcher.fill(5, 100, false, false);
+        assert_eq!(pending_heights(&prefetcher), vec![5, 6, 7]);
+    }
+}
@@ -0,0 +1,321 @@
+use crate::error::TokenAmountError;
+use num_bigint::{BigInt, Sign};
+use num_traits::{Signed, Zero};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// Unit
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// The units a TokenAmount can be formatted in or parsed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    AttoFil,
+    NanoFil,
+    Fil,
+}
+
+impl Unit {
+    // How many decimal places of attoFIL one of this unit is
+    pub fn decimals(self) -> u32 {
+        match self {
+            Unit::AttoFil => 0,
+            Unit::NanoFil => 9,
+            Unit::Fil => 18,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Unit::AttoFil => "attoFIL",
+            Unit::NanoFil => "nanoFIL",
+            Unit::Fil => "FIL",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Unit> {
+        match name.to_ascii_lowercase().as_str() {
+            "attofil" => Some(Unit::AttoFil),
+            "nanofil" => Some(Unit::NanoFil),
+            "fil" => Some(Unit::Fil),
+            _ => None,
+        }
+    }
+
+    fn scale(self) -> BigInt {
+        BigInt::from(10u8).pow(self.decimals())
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// TokenAmount
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// An amount of FIL, held exactly as a (possibly negative) number of attoFIL.
+//
+// In json (as Lotus sends values, gas prices and balances) a TokenAmount is the decimal
+// string of its attoFIL, e.g. "50000000000000000000".  In CBOR (deal proposals, actor
+// state) it is a byte string:  empty for zero, otherwise a sign byte (0 positive, 1
+// negative) followed by the big-endian magnitude.
+//
+// Display prints it in FIL the way Lotus does ("50 FIL", "0.000000001 FIL"); use
+// format() for attoFIL or nanoFIL.  FromStr takes a bare attoFIL number as well as a
+// number with a unit ("1.5 FIL", "10 nanoFIL").
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TokenAmount(BigInt);
+
+impl TokenAmount {
+    pub fn zero() -> TokenAmount {
+        TokenAmount(BigInt::zero())
+    }
+
+    pub fn from_atto<T: Into<BigInt>>(atto: T) -> TokenAmount {
+        TokenAmount(atto.into())
+    }
+
+    pub fn from_nano<T: Into<BigInt>>(nano: T) -> TokenAmount {
+        TokenAmount(nano.into() * Unit::NanoFil.scale())
+    }
+
+    pub fn from_whole<T: Into<BigInt>>(fil: T) -> TokenAmount {
+        TokenAmount(fil.into() * Unit::Fil.scale())
+    }
+
+    pub fn atto(&self) -> &BigInt {
+        &self.0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0.is_negative()
+    }
+
+    // The amount in `unit`, with as many decimal places as it needs and the unit's name
+    // after it, e.g. "1.5 FIL"
+    pub fn format(&self, unit: Unit) -> String {
+        let scale = unit.scale();
+        let magnitude = self.0.abs();
+        let whole = &magnitude / &scale;
+        let fraction = &magnitude % &scale;
+        let sign = if self.is_negative() { "-" } else { "" };
+        if fraction.is_zero() {
+            return format!("{}{} {}", sign, whole, unit.name());
+        }
+        let fraction = format!("{:0>width$}", fraction, width = unit.decimals() as usize);
+        format!("{}{}.{} {}", sign, whole, fraction.trim_end_matches('0'), unit.name())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<TokenAmount, TokenAmountError> {
+        let sign = match bytes.first() {
+            None => return Ok(TokenAmount::zero()),
+            Some(0) => Sign::Plus,
+            Some(1) => Sign::Minus,
+            Some(b) => return Err(TokenAmountError::InvalidBytes(format!("sign byte {}", b))),
+        };
+        Ok(TokenAmount(BigInt::from_bytes_be(sign, &bytes[1..])))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (sign, magnitude) = self.0.to_bytes_be();
+        match sign {
+            Sign::NoSign => Vec::new(),
+            Sign::Plus => std::iter::once(0).chain(magnitude).collect(),
+            Sign::Minus => std::iter::once(1).chain(magnitude).collect(),
+        }
+    }
+
+    pub fn from_cbor_value(value: &serde_cbor::Value) -> Result<TokenAmount, TokenAmountError> {
+        match value {
+            serde_cbor::Value::Bytes(bytes) => TokenAmount::from_bytes(bytes),
+            other => Err(TokenAmountError::InvalidBytes(format!("not a byte string: {:?}", other))),
+        }
+    }
+
+    pub fn to_cbor_value(&self) -> serde_cbor::Value {
+        serde_cbor::Value::Bytes(self.to_bytes())
+    }
+}
+
+impl std::fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.format(Unit::Fil))
+    }
+}
+
+impl std::str::FromStr for TokenAmount {
+    type Err = TokenAmountError;
+
+    fn from_str(s: &str) -> Result<TokenAmount, TokenAmountError> {
+        let invalid = || TokenAmountError::InvalidString(s.to_string());
+        let mut parts = s.split_whitespace();
+        let number = parts.next().ok_or_else(invalid)?;
+        let unit = match parts.next() {
+            Some(name) => Unit::from_name(name).ok_or_else(invalid)?,
+            None => Unit::AttoFil,
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        let (negative, number) = match number.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, number),
+        };
+        let (whole, fraction) = match number.find('.') {
+            Some(i) => (&number[..i], &number[i+1..]),
+            None => (number, ""),
+        };
+        let decimals = unit.decimals() as usize;
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() || !all_digits(whole) || !all_digits(fraction) || fraction.len() > decimals {
+            return Err(invalid());
+        }
+        let digits = format!("{}{:0<width$}", whole, fraction, width = decimals);
+        let atto : BigInt = digits.parse().map_err(|_| invalid())?;
+        Ok(TokenAmount(if negative { -atto } else { atto }))
+    }
+}
+
+impl Serialize for TokenAmount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for TokenAmount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<TokenAmount, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let atto : BigInt = s.parse().map_err(|_| serde::de::Error::custom(TokenAmountError::InvalidString(s)))?;
+        Ok(TokenAmount(atto))
+    }
+}
+
+impl std::ops::Add for TokenAmount {
+    type Output = TokenAmount;
+
+    fn add(self, other: TokenAmount) -> TokenAmount {
+        TokenAmount(self.0 + other.0)
+    }
+}
+
+impl<'a> std::ops::Add<&'a TokenAmount> for &'a TokenAmount {
+    type Output = TokenAmount;
+
+    fn add(self, other: &TokenAmount) -> TokenAmount {
+        TokenAmount(&self.0 + &other.0)
+    }
+}
+
+impl std::ops::AddAssign for TokenAmount {
+    fn add_assign(&mut self, other: TokenAmount) {
+        self.0 += other.0;
+    }
+}
+
+impl std::ops::Sub for TokenAmount {
+    type Output = TokenAmount;
+
+    fn sub(self, other: TokenAmount) -> TokenAmount {
+        TokenAmount(self.0 - other.0)
+    }
+}
+
+impl<'a> std::ops::Sub<&'a TokenAmount> for &'a TokenAmount {
+    type Output = TokenAmount;
+
+    fn sub(self, other: &TokenAmount) -> TokenAmount {
+        TokenAmount(&self.0 - &other.0)
+    }
+}
+
+impl std::ops::SubAssign for TokenAmount {
+    fn sub_assign(&mut self, other: TokenAmount) {
+        self.0 -= other.0;
+    }
+}
+
+// e.g. gas price * gas limit, or price per epoch * epochs
+impl std::ops::Mul<u64> for TokenAmount {
+    type Output = TokenAmount;
+
+    fn mul(self, factor: u64) -> TokenAmount {
+        TokenAmount(self.0 * factor)
+    }
+}
+
+impl<'a> std::ops::Mul<u64> for &'a TokenAmount {
+    type Output = TokenAmount;
+
+    fn mul(self, factor: u64) -> TokenAmount {
+        TokenAmount(&self.0 * factor)
+    }
+}
+
+impl std::ops::Neg for TokenAmount {
+    type Output = TokenAmount;
+
+    fn neg(self) -> TokenAmount {
+        TokenAmount(-self.0)
+    }
+}
+
+impl std::iter::Sum for TokenAmount {
+    fn sum<I: Iterator<Item = TokenAmount>>(iter: I) -> TokenAmount {
+        iter.fold(TokenAmount::zero(), |total, amount| total + amount)
+    }
+}
+
+impl<'a> std::iter::Sum<&'a TokenAmount> for TokenAmount {
+    fn sum<I: Iterator<Item = &'a TokenAmount>>(iter: I) -> TokenAmount {
+        iter.fold(TokenAmount::zero(), |total, amount| &total + amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_amount_strings() {
+        let amount : TokenAmount = "50000000000000000000".parse().unwrap();
+        assert_eq!(amount, TokenAmount::from_whole(50));
+        assert_eq!(amount.to_string(), "50 FIL");
+        assert_eq!(TokenAmount::from_atto(1500000000).format(Unit::NanoFil), "1.5 nanoFIL");
+        assert_eq!(TokenAmount::from_atto(1).to_string(), "0.000000000000000001 FIL");
+        assert_eq!(TokenAmount::from_atto(-1).format(Unit::AttoFil), "-1 attoFIL");
+        assert_eq!(TokenAmount::zero().to_string(), "0 FIL");
+
+        assert_eq!("1.5 FIL".parse::<TokenAmount>().unwrap(), TokenAmount::from_nano(1500000000u64));
+        assert_eq!("-10 nanofil".parse::<TokenAmount>().unwrap(), -TokenAmount::from_nano(10));
+        assert!("1.5".parse::<TokenAmount>().is_err(), "attoFIL has no fraction");
+        assert!("1.5 dogecoin".parse::<TokenAmount>().is_err());
+        assert!("0.0000000001 nanoFIL".parse::<TokenAmount>().is_err());
+
+        // json is the attoFIL string
+        let json = serde_json::to_string(&amount).unwrap();
+        assert_eq!(json, r#""50000000000000000000""#);
+        assert_eq!(serde_json::from_str::<TokenAmount>(&json).unwrap(), amount);
+    }
+
+    #[test]
+    fn test_token_amount_bytes_and_arithmetic() {
+        assert!(TokenAmount::from_bytes(&[]).unwrap().is_zero());
+        assert!(TokenAmount::zero().to_bytes().is_empty());
+        assert_eq!(TokenAmount::from_bytes(&[0, 1, 0]).unwrap(), TokenAmount::from_atto(256));
+        assert_eq!(TokenAmount::from_atto(-256).to_bytes(), vec![1, 1, 0]);
+        assert!(TokenAmount::from_bytes(&[2, 1]).is_err());
+
+        let price = TokenAmount::from_atto(100);
+        let total : TokenAmount = vec![price.clone() * 3, TokenAmount::from_atto(50)].iter().sum();
+        assert_eq!(total, TokenAmount::from_atto(350));
+        assert!(total > price);
+        assert_eq!(&total - &price, TokenAmount::from_atto(250));
+    }
+}
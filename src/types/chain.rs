@@ -0,0 +1,214 @@
+use crate::address::Address;
+use crate::tokenamount::TokenAmount;
+use serde::{Deserialize, Deserializer, Serialize};
+
+// Lotus encodes nil slices as `null` rather than `[]`; treat those as empty.
+pub(crate) fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where D: Deserializer<'de>, T: Default + Deserialize<'de>
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+////////////////////////////////////////////////////////
+///
+/// Cid / TipSetKey
+///
+////////////////////////////////////////////////////////
+
+// CIDs are in the `{"/": "bafy..."}` form the Lotus json api uses for all links
+pub use crate::cid::Cid;
+
+// The (sorted) block CIDs that identify a tipset.  An empty key means "the current head"
+// wherever the api accepts a tipset key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TipSetKey(#[serde(deserialize_with = "null_as_default")] pub Vec<Cid>);
+
+impl TipSetKey {
+    pub fn cids(&self) -> &[Cid] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+////////////////////////////////////////////////////////
+///
+/// TipSet / BlockHeader
+///
+////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TipSet {
+    #[serde(deserialize_with = "null_as_default")]
+    pub cids: Vec<Cid>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub blocks: Vec<BlockHeader>,
+    pub height: u64,
+}
+
+impl TipSet {
+    pub fn key(&self) -> TipSetKey {
+        TipSetKey(self.cids.clone())
+    }
+
+    // All blocks in a tipset share the same parents, so the first block's are the tipset's.
+    pub fn parents(&self) -> TipSetKey {
+        match self.blocks.first() {
+            Some(block) => TipSetKey(block.parents.clone()),
+            None => TipSetKey::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BlockHeader {
+    pub miner: String,
+    pub ticket: Option<Ticket>,
+    pub election_proof: Option<ElectionProof>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub beacon_entries: Vec<BeaconEntry>,
+    #[serde(rename = "WinPoStProof", default, deserialize_with = "null_as_default")]
+    pub win_post_proof: Vec<PoStProof>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub parents: Vec<Cid>,
+    pub parent_weight: String,
+    pub height: u64,
+    pub parent_state_root: Cid,
+    pub parent_message_receipts: Cid,
+    pub messages: Cid,
+    #[serde(rename = "BLSAggregate")]
+    pub bls_aggregate: Option<Signature>,
+    pub timestamp: u64,
+    pub block_sig: Option<Signature>,
+    #[serde(default)]
+    pub fork_signaling: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ticket {
+    #[serde(rename = "VRFProof")]
+    pub vrf_proof: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElectionProof {
+    #[serde(rename = "WinCount", default)]
+    pub win_count: i64,
+    #[serde(rename = "VRFProof")]
+    pub vrf_proof: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BeaconEntry {
+    pub round: u64,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoStProof {
+    #[serde(rename = "PoStProof")]
+    pub post_proof: i64,
+    #[serde(rename = "ProofBytes")]
+    pub proof_bytes: String,
+}
+
+// Type is 1 for secp256k1 and 2 for BLS; Data is the base64 signature bytes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Signature {
+    #[serde(rename = "Type")]
+    pub sig_type: u8,
+    #[serde(rename = "Data")]
+    pub data: String,
+}
+
+// One element of a Filecoin.ChainNotify notification.  The first notification is always a
+// single `current` change carrying the head; after that the node sends `revert`s for
+// tipsets dropped by a reorg followed by `apply`s for the new ones, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeadChange {
+    #[serde(rename = "Type")]
+    pub change_type: HeadChangeType,
+    #[serde(rename = "Val")]
+    pub val: TipSet,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeadChangeType {
+    Current,
+    Apply,
+    Revert,
+}
+
+////////////////////////////////////////////////////////
+///
+/// Messages / receipts
+///
+////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UnsignedMessage {
+    pub version: u64,
+    pub to: Address,
+    pub from: Address,
+    pub nonce: u64,
+    pub value: TokenAmount,
+    // absent after the switch to EIP-1559 style gas fees (gas_fee_cap/gas_premium)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_price: Option<TokenAmount>,
+    pub gas_limit: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_fee_cap: Option<TokenAmount>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_premium: Option<TokenAmount>,
+    pub method: u64,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub params: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SignedMessage {
+    pub message: UnsignedMessage,
+    pub signature: Signature,
+    // the signed message's CID, which newer nodes send with it (e.g. from the message pool)
+    #[serde(rename = "CID", default, skip_serializing_if = "Option::is_none")]
+    pub cid: Option<Cid>,
+}
+
+// Response of Filecoin.ChainGetBlockMessages.  Cids lists the BLS messages' CIDs first,
+// then the secp messages', in the same order as the two message lists.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BlockMessages {
+    #[serde(deserialize_with = "null_as_default")]
+    pub bls_messages: Vec<UnsignedMessage>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub secpk_messages: Vec<SignedMessage>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub cids: Vec<Cid>,
+}
+
+// One element of the Filecoin.ChainGetParentMessages response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ParentMessage {
+    pub cid: Cid,
+    pub message: UnsignedMessage,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MessageReceipt {
+    pub exit_code: i64,
+    #[serde(rename = "Return", default, deserialize_with = "null_as_default")]
+    pub ret: String,
+    pub gas_used: i64,
+}
@@ -0,0 +1,57 @@
+use crate::cbor::deal_proposal::DealProposal;
+use crate::tokenamount::TokenAmount;
+use serde::{Deserialize, Serialize};
+
+////////////////////////////////////////////////////////
+///
+/// Storage market
+///
+////////////////////////////////////////////////////////
+
+// One deal of the storage market actor:  Filecoin.StateMarketStorageDeal, or one value of
+// the Filecoin.StateMarketDeals map
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MarketDeal {
+    pub proposal: DealProposal,
+    pub state: DealState,
+}
+
+// Where a published deal is in its life.  Each epoch is -1 until it happens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DealState {
+    // when the provider's sector holding the deal was proven
+    pub sector_start_epoch: i64,
+    // when the market actor last paid out storage fees for the deal
+    pub last_updated_epoch: i64,
+    // when the deal was terminated early because its sector was
+    pub slash_epoch: i64,
+}
+
+impl DealState {
+    pub fn is_activated(&self) -> bool {
+        self.sector_start_epoch >= 0
+    }
+
+    pub fn is_slashed(&self) -> bool {
+        self.slash_epoch >= 0
+    }
+}
+
+// A participant's funds in the storage market actor (Filecoin.StateMarketBalance)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MarketBalance {
+    // everything deposited with AddBalance and not yet withdrawn
+    pub escrow: TokenAmount,
+    // the part of escrow held as collateral or owed for active deals
+    pub locked: TokenAmount,
+}
+
+impl MarketBalance {
+    // What WithdrawBalance could take out now
+    pub fn available(&self) -> TokenAmount {
+        &self.escrow - &self.locked
+    }
+}
@@ -0,0 +1,245 @@
+use crate::address::Address;
+use crate::cid::Cid;
+use crate::tokenamount::TokenAmount;
+use super::chain::null_as_default;
+use num_bigint::BigInt;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+////////////////////////////////////////////////////////
+///
+/// BitField
+///
+////////////////////////////////////////////////////////
+
+// A set of sector numbers (or partition indexes, ...), as the node sends faults,
+// recoveries and post submissions.  In json it is the lengths of alternating runs of
+// unset and set bits, starting with unset:  `[5, 3]` is {5, 6, 7} and `[0, 1]` is {0}.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BitField {
+    // the runs of set bits, in order, not touching
+    ranges: Vec<std::ops::Range<u64>>,
+}
+
+impl BitField {
+    pub fn new() -> BitField {
+        BitField::default()
+    }
+
+    // How many bits are set
+    pub fn len(&self) -> u64 {
+        self.ranges.iter().map(|range| range.end - range.start).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn contains(&self, bit: u64) -> bool {
+        self.ranges.iter().any(|range| range.contains(&bit))
+    }
+
+    // The set bits, in order
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.ranges.iter().flat_map(|range| range.clone())
+    }
+
+    pub fn ranges(&self) -> &[std::ops::Range<u64>] {
+        &self.ranges
+    }
+
+    fn from_run_lengths(run_lengths: &[u64]) -> BitField {
+        let mut ranges : Vec<std::ops::Range<u64>> = Vec::new();
+        let mut start = 0;
+        for (i, run_length) in run_lengths.iter().enumerate() {
+            let end = start + run_length;
+            if i % 2 == 1 && *run_length > 0 {
+                ranges.push(start..end);
+            }
+            start = end;
+        }
+        BitField{ ranges }
+    }
+
+    fn run_lengths(&self) -> Vec<u64> {
+        let mut run_lengths = Vec::new();
+        let mut end_of_last = 0;
+        for range in &self.ranges {
+            run_lengths.push(range.start - end_of_last);
+            run_lengths.push(range.end - range.start);
+            end_of_last = range.end;
+        }
+        run_lengths
+    }
+}
+
+impl std::iter::FromIterator<u64> for BitField {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> BitField {
+        let mut bits : Vec<u64> = iter.into_iter().collect();
+        bits.sort_unstable();
+        bits.dedup();
+        let mut ranges : Vec<std::ops::Range<u64>> = Vec::new();
+        for bit in bits {
+            match ranges.last_mut() {
+                Some(range) if range.end == bit => range.end += 1,
+                _ => ranges.push(bit..bit + 1),
+            }
+        }
+        BitField{ ranges }
+    }
+}
+
+impl Serialize for BitField {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.run_lengths().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BitField {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<BitField, D::Error> {
+        let run_lengths : Option<Vec<u64>> = Deserialize::deserialize(deserializer)?;
+        Ok(BitField::from_run_lengths(&run_lengths.unwrap_or_default()))
+    }
+}
+
+////////////////////////////////////////////////////////
+///
+/// Miner info / power
+///
+////////////////////////////////////////////////////////
+
+// Response of Filecoin.StateMinerInfo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MinerInfo {
+    pub owner: Address,
+    pub worker: Address,
+    // a worker change waiting for worker_change_epoch
+    #[serde(default, deserialize_with = "empty_address_as_none")]
+    pub new_worker: Option<Address>,
+    pub worker_change_epoch: i64,
+    #[serde(deserialize_with = "null_as_default")]
+    pub control_addresses: Vec<Address>,
+    pub peer_id: Option<String>,
+    // base64 of each multiaddr's bytes
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub multiaddrs: Vec<String>,
+    #[serde(rename = "WindowPoStProofType")]
+    pub window_post_proof_type: i64,
+    pub sector_size: u64,
+    #[serde(rename = "WindowPoStPartitionSectors")]
+    pub window_post_partition_sectors: u64,
+    pub consensus_fault_elapsed: i64,
+    // who receives the miner's rewards, sent by newer nodes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub beneficiary: Option<Address>,
+}
+
+// Lotus sends an unset address as "<empty>"
+fn empty_address_as_none<'de, D>(deserializer: D) -> Result<Option<Address>, D::Error>
+    where D: Deserializer<'de>
+{
+    match Option::<String>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(s) if s == "<empty>" || s.is_empty() => Ok(None),
+        Some(s) => s.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+// Response of Filecoin.StateMinerPower
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MinerPower {
+    pub miner_power: Claim,
+    pub total_power: Claim,
+    // whether the miner has enough power to win blocks
+    pub has_min_power: bool,
+}
+
+// Storage power in bytes.  The network's total is beyond u64, hence BigInt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Claim {
+    #[serde(with = "bigint_string")]
+    pub raw_byte_power: BigInt,
+    #[serde(with = "bigint_string")]
+    pub quality_adj_power: BigInt,
+}
+
+// Lotus sends big integers that aren't token amounts (power, deal weight) as decimal strings
+mod bigint_string {
+    use num_bigint::BigInt;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &BigInt, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigInt, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(|_| serde::de::Error::custom(format!("invalid big integer: {}", s)))
+    }
+}
+
+////////////////////////////////////////////////////////
+///
+/// Sectors / deadlines
+///
+////////////////////////////////////////////////////////
+
+// One sector of Filecoin.StateMinerSectors/StateMinerActiveSectors, or
+// Filecoin.StateSectorGetInfo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SectorOnChainInfo {
+    pub sector_number: u64,
+    pub seal_proof: i64,
+    #[serde(rename = "SealedCID")]
+    pub sealed_cid: Cid,
+    #[serde(rename = "DealIDs", deserialize_with = "null_as_default")]
+    pub deal_ids: Vec<u64>,
+    pub activation: i64,
+    pub expiration: i64,
+    #[serde(with = "bigint_string")]
+    pub deal_weight: BigInt,
+    #[serde(with = "bigint_string")]
+    pub verified_deal_weight: BigInt,
+    pub initial_pledge: TokenAmount,
+    pub expected_day_reward: TokenAmount,
+    pub expected_storage_pledge: TokenAmount,
+    // the sealed CID before a snap deal replaced the sector's data, sent by newer nodes
+    #[serde(rename = "SectorKeyCID", default, skip_serializing_if = "Option::is_none")]
+    pub sector_key_cid: Option<Cid>,
+}
+
+// One element of Filecoin.StateMinerDeadlines:  a miner's window post deadline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Deadline {
+    // the partitions that have had a proof submitted this proving period
+    pub post_submissions: BitField,
+    #[serde(default)]
+    pub disputable_proof_count: u64,
+}
+
+// Response of Filecoin.StateMinerProvingDeadline:  where a miner is in its proving period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DeadlineInfo {
+    pub current_epoch: i64,
+    pub period_start: i64,
+    // which of the deadlines is open
+    pub index: u64,
+    pub open: i64,
+    pub close: i64,
+    pub challenge: i64,
+    pub fault_cutoff: i64,
+    #[serde(rename = "WPoStPeriodDeadlines")]
+    pub wpost_period_deadlines: u64,
+    #[serde(rename = "WPoStProvingPeriod")]
+    pub wpost_proving_period: i64,
+    #[serde(rename = "WPoStChallengeWindow")]
+    pub wpost_challenge_window: i64,
+    #[serde(rename = "WPoStChallengeLookback")]
+    pub wpost_challenge_lookback: i64,
+    pub fault_declaration_cutoff: i64,
+}
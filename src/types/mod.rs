@@ -0,0 +1,266 @@
+pub mod chain;
+pub mod state;
+pub mod market;
+pub mod miner;
+pub mod mpool;
+
+pub use chain::*;
+pub use state::*;
+pub use market::*;
+pub use miner::*;
+pub use mpool::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tipset_from_chain_head_json() {
+        let json = r#"{
+            "Cids": [
+                { "/": "bafy2bzacebazmwzz7v5svaqwxhuk26jtco526k2vlzb5dt4ii6cuoszmq5hx4" }
+            ],
+            "Blocks": [
+                {
+                    "Miner": "t032055",
+                    "Ticket": { "VRFProof": "g1677uluuxPdmTZq" },
+                    "ElectionProof": { "VRFProof": "o+B9FEz5gtu3XQY5" },
+                    "BeaconEntries": [ { "Round": 180406, "Data": "mDE3PLUkCsu9guRK" } ],
+                    "WinPoStProof": [ { "PoStProof": 3, "ProofBytes": "gl3lowgpjwZgyyTF" } ],
+                    "Parents": [ { "/": "bafy2bzacedxg6sc42mcrwhlzvgjqvnxzgznwthp5yen2suyslwpdggf2fxou2" } ],
+                    "ParentWeight": "1689488189",
+                    "Height": 97222,
+                    "ParentStateRoot": { "/": "bafy2bzacebmjx4ipxmldy3zbnitstjcgxibyibbs64mvwvek6czxhskgawhmk" },
+                    "ParentMessageReceipts": { "/": "bafy2bzacebeapgu2cyzt5i4wfm44jior4u2akgwdzv7ko2hayslkzozeyl3pw" },
+                    "Messages": { "/": "bafy2bzaceb3xfzsg2xl4v5yfdecrqlpno4nly7j42zrkppozyp74fv7rlj7hc" },
+                    "BLSAggregate": { "Type": 2, "Data": "idZteeT5Nz2KHIZf" },
+                    "Timestamp": 1594955350,
+                    "BlockSig": { "Type": 2, "Data": "hu7rQakL9Mn8xW11" },
+                    "ForkSignaling": 0
+                }
+            ],
+            "Height": 97222
+        }"#;
+        let tipset : TipSet = serde_json::from_str(json).unwrap();
+        assert_eq!(tipset.height, 97222);
+        assert_eq!(tipset.key().cids()[0].to_string(), "bafy2bzacebazmwzz7v5svaqwxhuk26jtco526k2vlzb5dt4ii6cuoszmq5hx4");
+        assert_eq!(tipset.parents().cids()[0].to_string(), "bafy2bzacedxg6sc42mcrwhlzvgjqvnxzgznwthp5yen2suyslwpdggf2fxou2");
+        let block = &tipset.blocks[0];
+        assert_eq!(block.miner, "t032055");
+        assert_eq!(block.beacon_entries[0].round, 180406);
+        assert_eq!(block.win_post_proof[0].post_proof, 3);
+        assert_eq!(block.bls_aggregate.as_ref().unwrap().sig_type, 2);
+
+        // and back again, in the same json shape
+        let reencoded = serde_json::to_value(&tipset).unwrap();
+        assert_eq!(reencoded.pointer("/Cids/0/~1").unwrap(), "bafy2bzacebazmwzz7v5svaqwxhuk26jtco526k2vlzb5dt4ii6cuoszmq5hx4");
+        assert_eq!(reencoded.pointer("/Blocks/0/WinPoStProof/0/PoStProof").unwrap(), 3);
+    }
+
+    #[test]
+    fn test_block_messages_json() {
+        let json = r#"{
+            "BlsMessages": [
+                {
+                    "Version": 0,
+                    "To": "t01002",
+                    "From": "t3wowfuawcs6dwddo75jum2ddccrrq376oildvblukm4ctybuo5wtylvht5rahhtsoohoca3vdmyyt2zjlwqoa",
+                    "Nonce": 0,
+                    "Value": "0",
+                    "GasPrice": "0",
+                    "GasLimit": 99999999,
+                    "Method": 4,
+                    "Params": "gVgmACQIARIgxk09Mw1Kika9rMn0cUU05TvWiz3FmsJSiTqjTVoO34E="
+                }
+            ],
+            "SecpkMessages": [
+                {
+                    "Message": {
+                        "Version": 0,
+                        "To": "t01000",
+                        "From": "t1hw4amnow4gsgk2ottjdpdverfwhaznyrslsmoni",
+                        "Nonce": 1565,
+                        "Value": "50000000000000000000",
+                        "GasPrice": "0",
+                        "GasLimit": 10000,
+                        "Method": 0,
+                        "Params": null
+                    },
+                    "Signature": { "Type": 1, "Data": "8LHFp+nM5ozmQhnE" }
+                }
+            ],
+            "Cids": [
+                { "/": "bafy2bzaceahhze5r6ceij7ctcxadje2miweqxzcuuamzup2k5bb423tyoopve" },
+                { "/": "bafy2bzaced6u2e2s7uyy44lhavoexym7oyzlqnowa36ympogpfmqnzl5l2d36" }
+            ]
+        }"#;
+        let block_msgs : BlockMessages = serde_json::from_str(json).unwrap();
+        assert_eq!(block_msgs.bls_messages[0].method, 4);
+        assert_eq!(block_msgs.secpk_messages[0].message.nonce, 1565);
+        assert_eq!(block_msgs.secpk_messages[0].message.params, "");
+        assert_eq!(block_msgs.secpk_messages[0].signature.sig_type, 1);
+        assert_eq!(block_msgs.cids.len(), 2);
+
+        let empty : BlockMessages = serde_json::from_str(r#"{"BlsMessages":null,"SecpkMessages":[],"Cids":null}"#).unwrap();
+        assert!(empty.bls_messages.is_empty() && empty.cids.is_empty());
+    }
+
+    #[test]
+    fn test_parent_messages_and_receipts_json() {
+        let msgs : Vec<ParentMessage> = serde_json::from_str(r#"[{
+            "Cid": { "/": "bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i" },
+            "Message": {
+                "Version": 0, "To": "t04", "From": "t3rglqgjsidwb3l3j3uz52wylctnec2l5b4dtmoeebwslcktxzcvf7sdztqknhtxoyzizcuvetsdpa5cj743ea",
+                "Nonce": 20, "Value": "0", "GasPrice": "0", "GasLimit": 10000000, "Method": 2, "Params": "hVgxA4mX"
+            }
+        }]"#).unwrap();
+        assert_eq!(msgs[0].cid.to_string(), "bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i");
+        assert_eq!(msgs[0].message.to.to_string(), "t04");
+
+        let receipts : Vec<MessageReceipt> = serde_json::from_str(r#"[
+            { "ExitCode": 0, "Return": "gkMA0yhVApbyVpWf0hHUCUM+VYpeHKIODLXL", "GasUsed": 33928 },
+            { "ExitCode": 16, "Return": null, "GasUsed": 33483 }
+        ]"#).unwrap();
+        assert_eq!(receipts[0].gas_used, 33928);
+        assert_eq!(receipts[1].exit_code, 16);
+        assert_eq!(receipts[1].ret, "");
+    }
+
+    #[test]
+    fn test_head_change_json() {
+        let changes : Vec<HeadChange> = serde_json::from_str(r#"[
+            { "Type": "revert", "Val": { "Cids": [ { "/": "bafy2bzacebazmwzz7v5svaqwxhuk26jtco526k2vlzb5dt4ii6cuoszmq5hx4" } ], "Blocks": [], "Height": 10 } },
+            { "Type": "apply", "Val": { "Cids": [ { "/": "bafy2bzacedxg6sc42mcrwhlzvgjqvnxzgznwthp5yen2suyslwpdggf2fxou2" } ], "Blocks": [], "Height": 10 } }
+        ]"#).unwrap();
+        assert_eq!(changes[0].change_type, HeadChangeType::Revert);
+        assert_eq!(changes[1].change_type, HeadChangeType::Apply);
+        assert_eq!(changes[1].val.cids[0].to_string(), "bafy2bzacedxg6sc42mcrwhlzvgjqvnxzgznwthp5yen2suyslwpdggf2fxou2");
+    }
+
+    #[test]
+    fn test_actor_json() {
+        let json = r#"{
+            "Code": { "/": "bafkqaetgnfwc6mrpon2g64tbm5sw22lomvza" },
+            "Head": { "/": "bafy2bzacebazmwzz7v5svaqwxhuk26jtco526k2vlzb5dt4ii6cuoszmq5hx4" },
+            "Nonce": 0,
+            "Balance": "2500000000000000000"
+        }"#;
+        let actor : Actor = serde_json::from_str(json).unwrap();
+        let registry = crate::actors::MethodRegistry::new();
+        assert_eq!(registry.actor_type(&actor.code), Some(crate::actors::ActorType::Miner));
+        assert_eq!(actor.balance.to_string(), "2.5 FIL");
+        assert!(actor.address.is_none());
+    }
+
+    #[test]
+    fn test_msg_lookup_json() {
+        let json = r#"{
+            "Message": { "/": "bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i" },
+            "Receipt": { "ExitCode": 0, "Return": null, "GasUsed": 1694476 },
+            "ReturnDec": null,
+            "TipSet": [ { "/": "bafy2bzacebazmwzz7v5svaqwxhuk26jtco526k2vlzb5dt4ii6cuoszmq5hx4" } ],
+            "Height": 97222
+        }"#;
+        let lookup : MsgLookup = serde_json::from_str(json).unwrap();
+        assert_eq!(lookup.message.to_string(), "bafy2bzacebwydbfh32tilfdokxdm7u5vubvhoadhn6ieafd7dyh6s3q52h33i");
+        assert_eq!(lookup.receipt.exit_code, 0);
+        assert_eq!(lookup.receipt.ret, "");
+        assert_eq!(lookup.tipset.0.len(), 1);
+        assert_eq!(lookup.height, 97222);
+
+        // not found
+        let lookup : Option<MsgLookup> = serde_json::from_str("null").unwrap();
+        assert!(lookup.is_none());
+    }
+
+    #[test]
+    fn test_market_deals_json() {
+        let json = r#"{
+            "1033": {
+                "Proposal": {
+                    "PieceCID": { "/": "baga6ea4seaqhsxatjuqqcohz4akb6bd2fyhd3ujqu6oe5ywhjcddwjuu3fy6awa" },
+                    "PieceSize": 34359738368,
+                    "VerifiedDeal": true,
+                    "Client": "f01024",
+                    "Provider": "f01000",
+                    "Label": "mAXCg5AIg",
+                    "StartEpoch": 16182,
+                    "EndEpoch": 49956,
+                    "StoragePricePerEpoch": "500000000",
+                    "ProviderCollateral": "133169152",
+                    "ClientCollateral": "0"
+                },
+                "State": { "SectorStartEpoch": 16020, "LastUpdatedEpoch": -1, "SlashEpoch": -1 }
+            }
+        }"#;
+        let deals : std::collections::BTreeMap<u64, MarketDeal> = serde_json::from_str(json).unwrap();
+        let deal = &deals[&1033];
+        assert_eq!(deal.proposal.provider_addr.id(), Some(1000));
+        assert_eq!(deal.proposal.total_storage_fee().to_string(), "0.000016887 FIL");
+        assert!(deal.state.is_activated() && !deal.state.is_slashed());
+
+        let balance : MarketBalance = serde_json::from_str(r#"{ "Escrow": "1000", "Locked": "400" }"#).unwrap();
+        assert_eq!(balance.available(), crate::tokenamount::TokenAmount::from_atto(600));
+
+        let participants : std::collections::HashMap<crate::address::Address, MarketBalance> =
+            serde_json::from_str(r#"{ "f01000": { "Escrow": "1000", "Locked": "400" } }"#).unwrap();
+        assert_eq!(participants[&crate::address::Address::new_id(1000)], balance);
+    }
+
+    #[test]
+    fn test_bitfield_json() {
+        let faults : BitField = serde_json::from_str("[3, 2, 5, 1]").unwrap();
+        assert_eq!(faults.iter().collect::<Vec<u64>>(), vec![3, 4, 10]);
+        assert_eq!(faults.len(), 3);
+        assert!(faults.contains(10) && !faults.contains(5));
+        assert_eq!(serde_json::to_string(&faults).unwrap(), "[3,2,5,1]");
+        assert_eq!(vec![10, 3, 4].into_iter().collect::<BitField>(), faults);
+
+        assert_eq!(serde_json::to_string(&vec![0].into_iter().collect::<BitField>()).unwrap(), "[0,1]");
+        assert!(serde_json::from_str::<BitField>("null").unwrap().is_empty());
+        assert!(serde_json::from_str::<BitField>("[]").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_miner_json() {
+        let info : MinerInfo = serde_json::from_str(r#"{
+            "Owner": "f01001",
+            "Worker": "f01002",
+            "NewWorker": "<empty>",
+            "ControlAddresses": null,
+            "WorkerChangeEpoch": -1,
+            "PeerId": "12D3KooWPEMEb8bn4KbUFfVpbnMdTtC3vHaWsnaNKTtNZQcNSsHB",
+            "Multiaddrs": null,
+            "WindowPoStProofType": 8,
+            "SectorSize": 34359738368,
+            "WindowPoStPartitionSectors": 2349,
+            "ConsensusFaultElapsed": -1
+        }"#).unwrap();
+        assert_eq!(info.worker.id(), Some(1002));
+        assert!(info.new_worker.is_none() && info.control_addresses.is_empty() && info.multiaddrs.is_empty());
+
+        // more than u64 can hold
+        let power : MinerPower = serde_json::from_str(r#"{
+            "MinerPower": { "RawBytePower": "2336462209024", "QualityAdjPower": "2336462209024" },
+            "TotalPower": { "RawBytePower": "9851570995281920", "QualityAdjPower": "23048493023363072000" },
+            "HasMinPower": true
+        }"#).unwrap();
+        assert_eq!(power.total_power.quality_adj_power.to_string(), "23048493023363072000");
+
+        let sector : SectorOnChainInfo = serde_json::from_str(r#"{
+            "SectorNumber": 3,
+            "SealProof": 8,
+            "SealedCID": { "/": "bagboea4b5abcblraaktvfcyfrx4ugzc4t2bp3qftvhs35fkac3xmtbhal42a3wmd" },
+            "DealIDs": null,
+            "Activation": 16020,
+            "Expiration": 1570020,
+            "DealWeight": "0",
+            "VerifiedDealWeight": "0",
+            "InitialPledge": "158405640277856768",
+            "ExpectedDayReward": "1158018735651962",
+            "ExpectedStoragePledge": "22998932745466708"
+        }"#).unwrap();
+        assert_eq!(sector.sealed_cid.codec(), crate::cid::codec::FIL_COMMITMENT_SEALED);
+        assert!(sector.deal_ids.is_empty() && sector.sector_key_cid.is_none());
+    }
+}
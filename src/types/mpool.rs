@@ -0,0 +1,55 @@
+use crate::tokenamount::TokenAmount;
+use super::chain::SignedMessage;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+////////////////////////////////////////////////////////
+///
+/// Message pool
+///
+////////////////////////////////////////////////////////
+
+// One notification of Filecoin.MpoolSub:  a message entering or leaving this node's pool.
+// A message leaves when it is included in a block, replaced by one with the same nonce and
+// a higher fee, or pruned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MpoolUpdate {
+    #[serde(rename = "Type")]
+    pub update_type: MpoolChange,
+    #[serde(rename = "Message")]
+    pub message: SignedMessage,
+}
+
+// Lotus sends the kind of change as a number:  0 for add, 1 for remove
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpoolChange {
+    Add,
+    Remove,
+}
+
+impl Serialize for MpoolChange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MpoolChange::Add => serializer.serialize_u8(0),
+            MpoolChange::Remove => serializer.serialize_u8(1),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MpoolChange {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MpoolChange, D::Error> {
+        match u8::deserialize(deserializer)? {
+            0 => Ok(MpoolChange::Add),
+            1 => Ok(MpoolChange::Remove),
+            n => Err(serde::de::Error::custom(format!("unknown mpool change type: {}", n))),
+        }
+    }
+}
+
+// Limits for Filecoin.MpoolPushMessage when it fills in the gas fields
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MessageSendSpec {
+    // the most the message may pay in fees (gas_fee_cap * gas_limit); 0 for the node's
+    // configured default
+    pub max_fee: TokenAmount,
+}
@@ -0,0 +1,50 @@
+use crate::address::Address;
+use crate::cid::Cid;
+use crate::tokenamount::TokenAmount;
+use super::chain::{MessageReceipt, TipSetKey};
+use serde::{Deserialize, Serialize};
+
+////////////////////////////////////////////////////////
+///
+/// Actors
+///
+////////////////////////////////////////////////////////
+
+// Response of Filecoin.StateGetActor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Actor {
+    // Which builtin actor this is (see actors::MethodRegistry::actor_type())
+    pub code: Cid,
+    // Root of the actor's state
+    pub head: Cid,
+    pub nonce: u64,
+    pub balance: TokenAmount,
+    // The actor's robust (f4) address, sent by newer nodes for actors that have one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<Address>,
+}
+
+////////////////////////////////////////////////////////
+///
+/// Message lookup
+///
+////////////////////////////////////////////////////////
+
+// Response of Filecoin.StateSearchMsg/StateWaitMsg:  where a message was executed, and its
+// receipt.  It holds only the message's CID; use ApiClient::chain_get_message_typed() to
+// fetch the message itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MsgLookup {
+    // the CID of the message that was executed, as Lotus reports it:  not always the one
+    // looked up, as the node also finds a message that replaced it in the pool (same sender
+    // and nonce, higher fee)
+    pub message: Cid,
+    pub receipt: MessageReceipt,
+    // the tipset whose parent state includes the message's execution, i.e. the one after
+    // the tipset the message was included in
+    #[serde(rename = "TipSet")]
+    pub tipset: TipSetKey,
+    pub height: u64,
+}
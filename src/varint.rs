@@ -0,0 +1,29 @@
+// Unsigned LEB128 varints, as used in address payloads and in CIDs and multihashes
+
+pub(crate) fn encode(mut n: u64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+// The value and how many bytes it took, if `bytes` starts with a minimally encoded varint
+pub(crate) fn decode(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut n : u64 = 0;
+    for (i, byte) in bytes.iter().enumerate().take(10) {
+        n |= ((byte & 0x7f) as u64).checked_shl(7 * i as u32)?;
+        if byte & 0x80 == 0 {
+            if *byte == 0 && i > 0 {
+                return None;
+            }
+            return Some((n, i + 1));
+        }
+    }
+    None
+}
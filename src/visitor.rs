@@ -0,0 +1,439 @@
+use crate::blockanalyzer::Message;
+use crate::msgcache::IncompleteMessage;
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// ChainVisitor - receives the events of a blockchain walk
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// Implement only the events you care about; every method defaults to doing nothing.  Pass
+// the visitor to blockanalyzer::walk_blockchain() or blockanalyzer::follow_blockchain().
+//
+// Visitors compose:  a `(a, b)` tuple or a `Vec<Box<dyn ChainVisitor>>` is itself a visitor
+// that forwards each event to its members in order.
+pub trait ChainVisitor {
+    fn on_tipset_start(&mut self, _height: u64, _blocks: &Vec<String>) {}
+
+    fn on_block_start(&mut self, _blk_cid: &str) {}
+
+    // A message first seen in this block; it is not executed (and so has no receipt) yet
+    fn on_message_cid(&mut self, _msg_cid: &str) {}
+
+    // A message the current tipset executed, complete with its receipt and the tipsets that
+    // included and executed it.  Each is reported once per tipset, with the first block.
+    fn on_message(&mut self, _msg_cid: &str, _msg: &Message) {}
+
+    fn on_block_end(&mut self, _blk_cid: &str) {}
+
+    fn on_tipset_end(&mut self, _height: u64) {}
+
+    // A tipset visited earlier was dropped by a reorg
+    fn on_revert_tipset(&mut self, _height: u64, _blocks: &Vec<String>) {}
+
+    // No blocks were mined at this height; it gets no on_tipset_start/on_tipset_end
+    fn on_null_round(&mut self, _height: u64) {}
+
+    // A message reported with on_message_cid that no tipset executed within the walk's
+    // message cache epochs (see blockanalyzer::WalkOptions::message_cache_epochs())
+    fn on_unexecuted_message(&mut self, _msg_cid: &str, _msg: &IncompleteMessage) {}
+
+    // Whether the walker should fetch each block's new message cids at all.  Return false
+    // to save the api calls when on_message_cid does nothing.
+    fn wants_message_cids(&self) -> bool {
+        true
+    }
+
+    // Whether the walker should fetch each block's parent messages and receipts at all.
+    // Return false to save the api calls when on_message does nothing.
+    fn wants_messages(&self) -> bool {
+        true
+    }
+}
+
+impl<V: ChainVisitor + ?Sized> ChainVisitor for &mut V {
+    fn on_tipset_start(&mut self, height: u64, blocks: &Vec<String>) {
+        (**self).on_tipset_start(height, blocks)
+    }
+
+    fn on_block_start(&mut self, blk_cid: &str) {
+        (**self).on_block_start(blk_cid)
+    }
+
+    fn on_message_cid(&mut self, msg_cid: &str) {
+        (**self).on_message_cid(msg_cid)
+    }
+
+    fn on_message(&mut self, msg_cid: &str, msg: &Message) {
+        (**self).on_message(msg_cid, msg)
+    }
+
+    fn on_block_end(&mut self, blk_cid: &str) {
+        (**self).on_block_end(blk_cid)
+    }
+
+    fn on_tipset_end(&mut self, height: u64) {
+        (**self).on_tipset_end(height)
+    }
+
+    fn on_revert_tipset(&mut self, height: u64, blocks: &Vec<String>) {
+        (**self).on_revert_tipset(height, blocks)
+    }
+
+    fn on_null_round(&mut self, height: u64) {
+        (**self).on_null_round(height)
+    }
+
+    fn on_unexecuted_message(&mut self, msg_cid: &str, msg: &IncompleteMessage) {
+        (**self).on_unexecuted_message(msg_cid, msg)
+    }
+
+    fn wants_message_cids(&self) -> bool {
+        (**self).wants_message_cids()
+    }
+
+    fn wants_messages(&self) -> bool {
+        (**self).wants_messages()
+    }
+}
+
+impl<V: ChainVisitor + ?Sized> ChainVisitor for Box<V> {
+    fn on_tipset_start(&mut self, height: u64, blocks: &Vec<String>) {
+        (**self).on_tipset_start(height, blocks)
+    }
+
+    fn on_block_start(&mut self, blk_cid: &str) {
+        (**self).on_block_start(blk_cid)
+    }
+
+    fn on_message_cid(&mut self, msg_cid: &str) {
+        (**self).on_message_cid(msg_cid)
+    }
+
+    fn on_message(&mut self, msg_cid: &str, msg: &Message) {
+        (**self).on_message(msg_cid, msg)
+    }
+
+    fn on_block_end(&mut self, blk_cid: &str) {
+        (**self).on_block_end(blk_cid)
+    }
+
+    fn on_tipset_end(&mut self, height: u64) {
+        (**self).on_tipset_end(height)
+    }
+
+    fn on_revert_tipset(&mut self, height: u64, blocks: &Vec<String>) {
+        (**self).on_revert_tipset(height, blocks)
+    }
+
+    fn on_null_round(&mut self, height: u64) {
+        (**self).on_null_round(height)
+    }
+
+    fn on_unexecuted_message(&mut self, msg_cid: &str, msg: &IncompleteMessage) {
+        (**self).on_unexecuted_message(msg_cid, msg)
+    }
+
+    fn wants_message_cids(&self) -> bool {
+        (**self).wants_message_cids()
+    }
+
+    fn wants_messages(&self) -> bool {
+        (**self).wants_messages()
+    }
+}
+
+impl<A: ChainVisitor, B: ChainVisitor> ChainVisitor for (A, B) {
+    fn on_tipset_start(&mut self, height: u64, blocks: &Vec<String>) {
+        self.0.on_tipset_start(height, blocks);
+        self.1.on_tipset_start(height, blocks);
+    }
+
+    fn on_block_start(&mut self, blk_cid: &str) {
+        self.0.on_block_start(blk_cid);
+        self.1.on_block_start(blk_cid);
+    }
+
+    fn on_message_cid(&mut self, msg_cid: &str) {
+        self.0.on_message_cid(msg_cid);
+        self.1.on_message_cid(msg_cid);
+    }
+
+    fn on_message(&mut self, msg_cid: &str, msg: &Message) {
+        self.0.on_message(msg_cid, msg);
+        self.1.on_message(msg_cid, msg);
+    }
+
+    fn on_block_end(&mut self, blk_cid: &str) {
+        self.0.on_block_end(blk_cid);
+        self.1.on_block_end(blk_cid);
+    }
+
+    fn on_tipset_end(&mut self, height: u64) {
+        self.0.on_tipset_end(height);
+        self.1.on_tipset_end(height);
+    }
+
+    fn on_revert_tipset(&mut self, height: u64, blocks: &Vec<String>) {
+        self.0.on_revert_tipset(height, blocks);
+        self.1.on_revert_tipset(height, blocks);
+    }
+
+    fn on_null_round(&mut self, height: u64) {
+        self.0.on_null_round(height);
+        self.1.on_null_round(height);
+    }
+
+    fn on_unexecuted_message(&mut self, msg_cid: &str, msg: &IncompleteMessage) {
+        self.0.on_unexecuted_message(msg_cid, msg);
+        self.1.on_unexecuted_message(msg_cid, msg);
+    }
+
+    fn wants_message_cids(&self) -> bool {
+        self.0.wants_message_cids() || self.1.wants_message_cids()
+    }
+
+    fn wants_messages(&self) -> bool {
+        self.0.wants_messages() || self.1.wants_messages()
+    }
+}
+
+impl ChainVisitor for Vec<Box<dyn ChainVisitor>> {
+    fn on_tipset_start(&mut self, height: u64, blocks: &Vec<String>) {
+        self.iter_mut().for_each(|v| v.on_tipset_start(height, blocks));
+    }
+
+    fn on_block_start(&mut self, blk_cid: &str) {
+        self.iter_mut().for_each(|v| v.on_block_start(blk_cid));
+    }
+
+    fn on_message_cid(&mut self, msg_cid: &str) {
+        self.iter_mut().for_each(|v| v.on_message_cid(msg_cid));
+    }
+
+    fn on_message(&mut self, msg_cid: &str, msg: &Message) {
+        self.iter_mut().for_each(|v| v.on_message(msg_cid, msg));
+    }
+
+    fn on_block_end(&mut self, blk_cid: &str) {
+        self.iter_mut().for_each(|v| v.on_block_end(blk_cid));
+    }
+
+    fn on_tipset_end(&mut self, height: u64) {
+        self.iter_mut().for_each(|v| v.on_tipset_end(height));
+    }
+
+    fn on_revert_tipset(&mut self, height: u64, blocks: &Vec<String>) {
+        self.iter_mut().for_each(|v| v.on_revert_tipset(height, blocks));
+    }
+
+    fn on_null_round(&mut self, height: u64) {
+        self.iter_mut().for_each(|v| v.on_null_round(height));
+    }
+
+    fn on_unexecuted_message(&mut self, msg_cid: &str, msg: &IncompleteMessage) {
+        self.iter_mut().for_each(|v| v.on_unexecuted_message(msg_cid, msg));
+    }
+
+    fn wants_message_cids(&self) -> bool {
+        self.iter().any(|v| v.wants_message_cids())
+    }
+
+    fn wants_messages(&self) -> bool {
+        self.iter().any(|v| v.wants_messages())
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// CallbackVisitor - adapts the Option<closure> arguments of iterate_over_blockchain()
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// Lets the iterate_over_blockchain() style of callbacks be used wherever a visitor is taken,
+// e.g. to call walk_blockchain() with WalkOptions:
+//
+//     let mut on_finished_tipset = |height: u64| println!("done with {}", height);
+//     walk_blockchain(0, 100, &api, &options, &mut CallbackVisitor{
+//         on_finished_tipset: Some(&mut on_finished_tipset),
+//         ..CallbackVisitor::none()
+//     })?;
+pub struct CallbackVisitor<'a> {
+    pub on_starting_new_tipset:     std::option::Option<&'a mut dyn FnMut(u64, &Vec<String>)>,
+    pub on_starting_block:          std::option::Option<&'a mut dyn FnMut(&str)>,
+    pub on_found_new_message_cid:   std::option::Option<&'a mut dyn FnMut(&str)>,
+    pub on_found_new_message:       std::option::Option<&'a mut dyn FnMut(&str, &Message)>,
+    pub on_finished_block:          std::option::Option<&'a mut dyn FnMut(&str)>,
+    pub on_finished_tipset:         std::option::Option<&'a mut dyn FnMut(u64)>,
+    pub on_revert_tipset:           std::option::Option<&'a mut dyn FnMut(u64, &Vec<String>)>,
+    pub on_null_round:              std::option::Option<&'a mut dyn FnMut(u64)>,
+    pub on_unexecuted_message:      std::option::Option<&'a mut dyn FnMut(&str, &IncompleteMessage)>,
+}
+
+impl<'a> CallbackVisitor<'a> {
+    pub fn none() -> CallbackVisitor<'a> {
+        CallbackVisitor{
+            on_starting_new_tipset: None, on_starting_block: None, on_found_new_message_cid: None,
+            on_found_new_message: None, on_finished_block: None, on_finished_tipset: None,
+            on_revert_tipset: None, on_null_round: None, on_unexecuted_message: None,
+        }
+    }
+}
+
+impl<'a> ChainVisitor for CallbackVisitor<'a> {
+    fn on_tipset_start(&mut self, height: u64, blocks: &Vec<String>) {
+        if let Some(f) = self.on_starting_new_tipset.as_mut() {
+            f(height, blocks);
+        }
+    }
+
+    fn on_block_start(&mut self, blk_cid: &str) {
+        if let Some(f) = self.on_starting_block.as_mut() {
+            f(blk_cid);
+        }
+    }
+
+    fn on_message_cid(&mut self, msg_cid: &str) {
+        if let Some(f) = self.on_found_new_message_cid.as_mut() {
+            f(msg_cid);
+        }
+    }
+
+    fn on_message(&mut self, msg_cid: &str, msg: &Message) {
+        if let Some(f) = self.on_found_new_message.as_mut() {
+            f(msg_cid, msg);
+        }
+    }
+
+    fn on_block_end(&mut self, blk_cid: &str) {
+        if let Some(f) = self.on_finished_block.as_mut() {
+            f(blk_cid);
+        }
+    }
+
+    fn on_tipset_end(&mut self, height: u64) {
+        if let Some(f) = self.on_finished_tipset.as_mut() {
+            f(height);
+        }
+    }
+
+    fn on_revert_tipset(&mut self, height: u64, blocks: &Vec<String>) {
+        if let Some(f) = self.on_revert_tipset.as_mut() {
+            f(height, blocks);
+        }
+    }
+
+    fn on_null_round(&mut self, height: u64) {
+        if let Some(f) = self.on_null_round.as_mut() {
+            f(height);
+        }
+    }
+
+    fn on_unexecuted_message(&mut self, msg_cid: &str, msg: &IncompleteMessage) {
+        if let Some(f) = self.on_unexecuted_message.as_mut() {
+            f(msg_cid, msg);
+        }
+    }
+
+    fn wants_message_cids(&self) -> bool {
+        self.on_found_new_message_cid.is_some()
+    }
+
+    fn wants_messages(&self) -> bool {
+        self.on_found_new_message.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl ChainVisitor for Recorder {
+        fn on_tipset_start(&mut self, height: u64, blocks: &Vec<String>) {
+            self.events.push(format!("tipset_start {} {}", height, blocks.len()));
+        }
+
+        fn on_block_end(&mut self, blk_cid: &str) {
+            self.events.push(format!("block_end {}", blk_cid));
+        }
+
+        fn wants_messages(&self) -> bool {
+            false
+        }
+    }
+
+    #[derive(Default)]
+    struct TipsetCounter {
+        tipsets: u64,
+        reverted: u64,
+    }
+
+    impl ChainVisitor for TipsetCounter {
+        fn on_tipset_end(&mut self, _height: u64) {
+            self.tipsets += 1;
+        }
+
+        fn on_revert_tipset(&mut self, _height: u64, _blocks: &Vec<String>) {
+            self.reverted += 1;
+        }
+
+        fn wants_message_cids(&self) -> bool {
+            false
+        }
+
+        fn wants_messages(&self) -> bool {
+            false
+        }
+    }
+
+    fn drive(visitor: &mut dyn ChainVisitor) {
+        let blocks = vec!["bafy1".to_string(), "bafy2".to_string()];
+        visitor.on_tipset_start(10, &blocks);
+        for blk in &blocks {
+            visitor.on_block_start(blk);
+            visitor.on_block_end(blk);
+        }
+        visitor.on_tipset_end(10);
+        visitor.on_revert_tipset(10, &blocks);
+    }
+
+    #[test]
+    fn test_tuple_forwards_to_both() {
+        let mut recorder = Recorder::default();
+        let mut counter = TipsetCounter::default();
+        {
+            let mut both = (&mut recorder, &mut counter);
+            assert!(both.wants_message_cids());
+            assert!(!both.wants_messages());
+            drive(&mut both);
+        }
+        assert_eq!(recorder.events, vec!["tipset_start 10 2", "block_end bafy1", "block_end bafy2"]);
+        assert_eq!(counter.tipsets, 1);
+        assert_eq!(counter.reverted, 1);
+    }
+
+    #[test]
+    fn test_vec_of_visitors() {
+        let mut visitors : Vec<Box<dyn ChainVisitor>> = vec![
+            Box::new(TipsetCounter::default()),
+            Box::new(TipsetCounter::default()),
+        ];
+        assert!(!visitors.wants_message_cids());
+        drive(&mut visitors);
+
+        let mut callback_heights = vec![];
+        let mut on_finished_tipset = |height: u64| callback_heights.push(height);
+        let mut callbacks = CallbackVisitor{
+            on_finished_tipset: Some(&mut on_finished_tipset),
+            ..CallbackVisitor::none()
+        };
+        assert!(!callbacks.wants_messages());
+        drive(&mut callbacks);
+        assert_eq!(callback_heights, vec![10]);
+    }
+}
@@ -0,0 +1,363 @@
+use crate::api::ApiClient;
+use crate::blockanalyzer::{CancelHandle, FollowMode, HeightWalker, Message, WalkOptions};
+use crate::error::WalkError;
+use crate::msgcache::{IncompleteMessage, MessageCacheMetrics};
+use crate::visitor::ChainVisitor;
+use std::collections::VecDeque;
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// ChainEvent - one step of a blockchain walk
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// The events a walk produces, in the order the ChainVisitor methods of the same name would be
+// called.
+#[derive(Debug, Clone)]
+pub enum ChainEvent {
+    TipsetStart { height: u64, blocks: Vec<String> },
+    BlockStart { blk_cid: String },
+    // A message first seen in this block; not executed (and so without a receipt) yet
+    MessageCid { msg_cid: String },
+    // A message the current tipset executed, complete with its receipt; once per tipset
+    Message { msg_cid: String, message: Message },
+    BlockEnd { blk_cid: String },
+    TipsetEnd { height: u64 },
+    // A tipset reported earlier was dropped by a reorg
+    RevertTipset { height: u64, blocks: Vec<String> },
+    // No blocks were mined at this height; there is no TipsetStart/TipsetEnd for it
+    NullRound { height: u64 },
+    // A message from a MessageCid event that no tipset executed in time
+    UnexecutedMessage { msg_cid: String, message: IncompleteMessage },
+}
+
+impl ChainEvent {
+    // Replays this event into `visitor`
+    pub fn visit(&self, visitor: &mut dyn ChainVisitor) {
+        match self {
+            ChainEvent::TipsetStart{height, blocks} => visitor.on_tipset_start(*height, blocks),
+            ChainEvent::BlockStart{blk_cid} => visitor.on_block_start(blk_cid),
+            ChainEvent::MessageCid{msg_cid} => visitor.on_message_cid(msg_cid),
+            ChainEvent::Message{msg_cid, message} => visitor.on_message(msg_cid, message),
+            ChainEvent::BlockEnd{blk_cid} => visitor.on_block_end(blk_cid),
+            ChainEvent::TipsetEnd{height} => visitor.on_tipset_end(*height),
+            ChainEvent::RevertTipset{height, blocks} => visitor.on_revert_tipset(*height, blocks),
+            ChainEvent::NullRound{height} => visitor.on_null_round(*height),
+            ChainEvent::UnexecutedMessage{msg_cid, message} => visitor.on_unexecuted_message(msg_cid, message),
+        }
+    }
+
+    // The last event of a height, after which the walk may checkpoint it
+    fn ends_height(&self) -> bool {
+        matches!(self, ChainEvent::TipsetEnd{..} | ChainEvent::NullRound{..})
+    }
+}
+
+// Collects a step's worth of visitor calls as events
+struct EventBuffer {
+    events:         VecDeque<ChainEvent>,
+    message_cids:   bool,
+    messages:       bool,
+}
+
+impl ChainVisitor for EventBuffer {
+    fn on_tipset_start(&mut self, height: u64, blocks: &Vec<String>) {
+        self.events.push_back(ChainEvent::TipsetStart{ height, blocks: blocks.clone() });
+    }
+
+    fn on_block_start(&mut self, blk_cid: &str) {
+        self.events.push_back(ChainEvent::BlockStart{ blk_cid: blk_cid.to_string() });
+    }
+
+    fn on_message_cid(&mut self, msg_cid: &str) {
+        if self.message_cids {
+            self.events.push_back(ChainEvent::MessageCid{ msg_cid: msg_cid.to_string() });
+        }
+    }
+
+    fn on_message(&mut self, msg_cid: &str, msg: &Message) {
+        self.events.push_back(ChainEvent::Message{ msg_cid: msg_cid.to_string(), message: msg.clone() });
+    }
+
+    fn on_block_end(&mut self, blk_cid: &str) {
+        self.events.push_back(ChainEvent::BlockEnd{ blk_cid: blk_cid.to_string() });
+    }
+
+    fn on_tipset_end(&mut self, height: u64) {
+        self.events.push_back(ChainEvent::TipsetEnd{ height });
+    }
+
+    fn on_revert_tipset(&mut self, height: u64, blocks: &Vec<String>) {
+        self.events.push_back(ChainEvent::RevertTipset{ height, blocks: blocks.clone() });
+    }
+
+    fn on_null_round(&mut self, height: u64) {
+        self.events.push_back(ChainEvent::NullRound{ height });
+    }
+
+    fn on_unexecuted_message(&mut self, msg_cid: &str, msg: &IncompleteMessage) {
+        if self.message_cids {
+            self.events.push_back(ChainEvent::UnexecutedMessage{ msg_cid: msg_cid.to_string(), message: msg.clone() });
+        }
+    }
+
+    fn wants_message_cids(&self) -> bool {
+        self.message_cids
+    }
+
+    fn wants_messages(&self) -> bool {
+        self.messages
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// ChainWalker - the walk as an Iterator of ChainEvents
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// Pulls one tipset at a time from the node and hands out its events in order, e.g.
+//
+//     let new_msgs = ChainWalker::new(&api, 0, 100)
+//         .filter(|ev| matches!(ev, Ok(ChainEvent::MessageCid{..})))
+//         .count();
+//
+// Errors are handled per WalkOptions::default() unless options() says otherwise; one the
+// policy aborts on is yielded as an Err, after which the walker yields nothing more.
+//
+// Both kinds of message events are on by default; turn off the ones you don't use with
+// message_cids(false) / messages(false) to save the api calls behind them.
+pub struct ChainWalker<'a> {
+    heights:    HeightWalker<'a>,
+    buffer:     EventBuffer,
+    finished:   bool,
+}
+
+impl<'a> ChainWalker<'a> {
+    // Walks from `iterate_from_min_height` to `iterate_to_max_height` (or the head, if lower)
+    pub fn new(api: &'a ApiClient, iterate_from_min_height: u64, iterate_to_max_height: u64) -> ChainWalker<'a> {
+        ChainWalker::with_heights(HeightWalker::new(iterate_from_min_height, Some(iterate_to_max_height), api, None,
+            &CancelHandle::new(), &WalkOptions::default()))
+    }
+
+    // Walks from `iterate_from_min_height` and then keeps following the head (see
+    // blockanalyzer::follow_blockchain()); next() blocks while waiting for new tipsets and
+    // returns None once `cancel` is cancelled.
+    pub fn follow(api: &'a ApiClient, iterate_from_min_height: u64, follow_mode: FollowMode, cancel: &CancelHandle) -> ChainWalker<'a> {
+        ChainWalker::with_heights(HeightWalker::new(iterate_from_min_height, None, api, Some(&follow_mode),
+            cancel, &WalkOptions::default()))
+    }
+
+    fn with_heights(heights: HeightWalker<'a>) -> ChainWalker<'a> {
+        ChainWalker{
+            heights,
+            buffer: EventBuffer{ events: VecDeque::new(), message_cids: true, messages: true },
+            finished: false,
+        }
+    }
+
+    // The error policy and prefetch depth to use (see blockanalyzer::WalkOptions)
+    pub fn options(mut self, options: &WalkOptions) -> ChainWalker<'a> {
+        self.heights.set_options(options);
+        self
+    }
+
+    // Whether to yield ChainEvent::MessageCid events
+    pub fn message_cids(mut self, enabled: bool) -> ChainWalker<'a> {
+        self.buffer.message_cids = enabled;
+        self
+    }
+
+    // Whether to yield ChainEvent::Message events
+    pub fn messages(mut self, enabled: bool) -> ChainWalker<'a> {
+        self.buffer.messages = enabled;
+        self
+    }
+
+    // What has happened to the messages seen so far (see msgcache::IncompleteMessageCache)
+    pub fn message_cache_metrics(&self) -> MessageCacheMetrics {
+        self.heights.message_cache_metrics()
+    }
+}
+
+impl<'a> Iterator for ChainWalker<'a> {
+    type Item = Result<ChainEvent, WalkError>;
+
+    fn next(&mut self) -> Option<Result<ChainEvent, WalkError>> {
+        loop {
+            if let Some(event) = self.buffer.events.pop_front() {
+                return Some(Ok(event));
+            }
+            // Everything the last step produced has been handed out and (as far as we can
+            // tell) handled, so its tipset can be checkpointed
+            if let Err(e) = self.heights.commit_checkpoint() {
+                self.finished = true;
+                return Some(Err(e));
+            }
+            if self.finished {
+                return None;
+            }
+            match self.heights.step(&mut self.buffer) {
+                Ok(true) => {},
+                Ok(false) => self.finished = true,
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                },
+            }
+        }
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// ChainEventStream - the walk as an async Stream of ChainEvents
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// How many events the background walk may get ahead of the consumer
+const EVENT_STREAM_BUFFER : usize = 256;
+
+// Runs a ChainWalker on a background thread (with its own clone of the ApiClient) and
+// yields its events as a futures::Stream.  The walk stops once the stream is dropped.
+//
+// With a checkpoint store, the walk waits after each TipsetEnd (or NullRound) until the
+// consumer polls for the next event, so a height is only checkpointed once the consumer is
+// done with it.
+pub struct ChainEventStream {
+    receiver:       futures::channel::mpsc::Receiver<Result<ChainEvent, WalkError>>,
+    cancel:         CancelHandle,
+    // Tells the walk the consumer is done with the last height (only when checkpointing)
+    tipset_acks:    Option<std::sync::mpsc::Sender<()>>,
+    awaiting_ack:   bool,
+}
+
+impl ChainEventStream {
+    // Like ChainWalker::new(), with both kinds of message events on
+    pub fn new(api: &ApiClient, iterate_from_min_height: u64, iterate_to_max_height: u64, options: &WalkOptions) -> ChainEventStream {
+        let options = options.clone();
+        let checkpointing = options.has_checkpoint_store();
+        ChainEventStream::spawn(api, checkpointing, move |api, _cancel| {
+            ChainWalker::new(api, iterate_from_min_height, iterate_to_max_height).options(&options)
+        })
+    }
+
+    // Like ChainWalker::follow(); the stream ends once cancel() is called
+    pub fn follow(api: &ApiClient, iterate_from_min_height: u64, follow_mode: FollowMode, options: &WalkOptions) -> ChainEventStream {
+        let options = options.clone();
+        let checkpointing = options.has_checkpoint_store();
+        ChainEventStream::spawn(api, checkpointing, move |api, cancel| {
+            ChainWalker::follow(api, iterate_from_min_height, follow_mode, cancel).options(&options)
+        })
+    }
+
+    // Stops the walk; the stream ends after the events already queued
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    fn spawn<F>(api: &ApiClient, checkpointing: bool, make_walker: F) -> ChainEventStream
+        where F: for<'w> FnOnce(&'w ApiClient, &CancelHandle) -> ChainWalker<'w> + Send + 'static
+    {
+        use futures::SinkExt;
+
+        let (mut sender, receiver) = futures::channel::mpsc::channel(EVENT_STREAM_BUFFER);
+        let (ack_sender, ack_receiver) = std::sync::mpsc::channel::<()>();
+        let cancel = CancelHandle::new();
+        let walk_cancel = cancel.clone();
+        let api = api.clone();
+        std::thread::spawn(move || {
+            let walker = make_walker(&api, &walk_cancel);
+            for event in walker {
+                let ends_height = event.as_ref().map(ChainEvent::ends_height).unwrap_or(false);
+                if walk_cancel.is_cancelled() || async_std::task::block_on(sender.send(event)).is_err() {
+                    break
+                }
+                if checkpointing && ends_height && ack_receiver.recv().is_err() {
+                    break
+                }
+            }
+        });
+        ChainEventStream{
+            receiver,
+            cancel,
+            tipset_acks: if checkpointing { Some(ack_sender) } else { None },
+            awaiting_ack: false,
+        }
+    }
+}
+
+impl futures::Stream for ChainEventStream {
+    type Item = Result<ChainEvent, WalkError>;
+
+    fn poll_next(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context) -> std::task::Poll<Option<Result<ChainEvent, WalkError>>> {
+        use futures::StreamExt;
+        if self.awaiting_ack {
+            self.awaiting_ack = false;
+            if let Some(tipset_acks) = &self.tipset_acks {
+                let _ = tipset_acks.send(());
+            }
+        }
+        let next = self.receiver.poll_next_unpin(cx);
+        if let std::task::Poll::Ready(Some(Ok(event))) = &next {
+            self.awaiting_ack = event.ends_height() && self.tipset_acks.is_some();
+        }
+        next
+    }
+}
+
+impl Drop for ChainEventStream {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl ChainVisitor for Recorder {
+        fn on_tipset_start(&mut self, height: u64, blocks: &Vec<String>) {
+            self.events.push(format!("tipset_start {} {}", height, blocks.join(",")));
+        }
+
+        fn on_message_cid(&mut self, msg_cid: &str) {
+            self.events.push(format!("message_cid {}", msg_cid));
+        }
+
+        fn on_tipset_end(&mut self, height: u64) {
+            self.events.push(format!("tipset_end {}", height));
+        }
+
+        fn on_revert_tipset(&mut self, height: u64, _blocks: &Vec<String>) {
+            self.events.push(format!("revert {}", height));
+        }
+
+        fn on_null_round(&mut self, height: u64) {
+            self.events.push(format!("null_round {}", height));
+        }
+    }
+
+    #[test]
+    fn test_event_buffer_round_trip() {
+        let mut buffer = EventBuffer{ events: VecDeque::new(), message_cids: false, messages: true };
+        let blocks = vec!["bafy1".to_string()];
+        buffer.on_tipset_start(7, &blocks);
+        buffer.on_message_cid("bafymsg");
+        buffer.on_tipset_end(7);
+        buffer.on_revert_tipset(7, &blocks);
+        buffer.on_null_round(8);
+        assert_eq!(buffer.events.len(), 4, "message cids are dropped when disabled");
+
+        let mut recorder = Recorder::default();
+        for event in &buffer.events {
+            event.visit(&mut recorder);
+        }
+        assert_eq!(recorder.events, vec!["tipset_start 7 bafy1", "tipset_end 7", "revert 7", "null_round 8"]);
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use futures::{SinkExt, StreamExt};
+use futures::channel::{mpsc, oneshot};
+use async_tungstenite::tungstenite::Message as WsMessage;
+use serde_json::json;
+use log;
+use crate::error::LotusError;
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// WsClient - JSON RPC over a websocket, with Lotus's channel subscriptions
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// Lotus (go-jsonrpc) does not use JSON RPC subscriptions.  A method returning a channel,
+// such as Filecoin.ChainNotify, answers the request with a channel id:
+//
+//      --> { "jsonrpc": "2.0", "method": "Filecoin.ChainNotify", "params": [], "id": 1 }
+//      <-- { "jsonrpc": "2.0", "result": 7, "id": 1 }
+//
+// and then pushes each value sent on the channel as a call from the server:
+//
+//      <-- { "jsonrpc": "2.0", "method": "xrpc.ch.val", "params": [7, <value>] }
+//      <-- { "jsonrpc": "2.0", "method": "xrpc.ch.close", "params": [7] }
+//
+// The client stops a channel by cancelling the original request:
+//
+//      --> { "jsonrpc": "2.0", "method": "xrpc.cancel", "params": [1] }
+//
+// Plain requests work as usual, so both kinds share one connection here.
+
+type WsStream = async_tungstenite::WebSocketStream<async_tungstenite::async_std::ConnectStream>;
+type WsSink = futures::stream::SplitSink<WsStream, WsMessage>;
+type ChannelReceiver = mpsc::UnboundedReceiver<jsonrpsee::common::JsonValue>;
+
+enum PendingRequest {
+    Call(oneshot::Sender<Result<jsonrpsee::common::JsonValue, LotusError>>),
+    Subscribe(oneshot::Sender<Result<(u64, ChannelReceiver), LotusError>>),
+}
+
+// Owned by the WsClients and Subscriptions of one connection; the read loop only has a Weak
+// reference, so the connection is closed (see Drop) once the last of them is dropped.
+struct WsShared {
+    // None only while being dropped
+    writer: async_std::sync::Mutex<Option<WsSink>>,
+    pending: std::sync::Mutex<HashMap<u64, PendingRequest>>,
+    channels: std::sync::Mutex<HashMap<u64, mpsc::UnboundedSender<jsonrpsee::common::JsonValue>>>,
+    next_id: AtomicU64,
+    closed: AtomicBool,
+    // dropped with the WsShared, which tells the read loop to stop
+    _stop_read_loop: oneshot::Sender<()>,
+}
+
+#[derive(Clone)]
+pub struct WsClient {
+    shared: Arc<WsShared>,
+    timeout: std::time::Duration,
+}
+
+impl WsClient {
+    // Opens a websocket to `ws_url` (e.g. ws://lotus1:1234/rpc/v0), sending auth_token (if
+    // not "") as `Authorization: Bearer <token>` on the handshake.  `timeout` bounds the wait
+    // for each request's answer, not the lifetime of subscriptions.
+    pub async fn connect(ws_url: &str, auth_token: &str, timeout: std::time::Duration) -> Result<WsClient, LotusError> {
+        use async_tungstenite::tungstenite::client::IntoClientRequest;
+        use async_tungstenite::tungstenite::http::HeaderValue;
+
+        let mut request = ws_url.into_client_request()
+            .map_err(|e| LotusError::Config(format!("bad websocket url '{}': {}",ws_url,e)))?;
+        if auth_token != "" {
+            let header_value = HeaderValue::from_str(&format!("Bearer {}",auth_token))
+                .map_err(|e| LotusError::Config(format!("bad auth token: {}",e)))?;
+            request.headers_mut().insert("Authorization", header_value);
+        }
+
+        let connect = async_tungstenite::async_std::connect_async(request);
+        let (ws_stream, _) = match async_std::future::timeout(timeout, connect).await {
+            Ok(Ok(connected)) => connected,
+            Ok(Err(e)) => return Err(LotusError::Transport(format!("websocket connect to '{}' failed: {}",ws_url,e))),
+            Err(_) => return Err(LotusError::Timeout(timeout)),
+        };
+        log::debug!("WsClient::connect: connected to '{}'",ws_url);
+
+        let (ws_client, _read_loop) = WsClient::from_stream(ws_stream, timeout);
+        Ok(ws_client)
+    }
+
+    // Starts the read loop on a connected websocket; its handle finishes when the loop ends
+    fn from_stream(ws_stream: WsStream, timeout: std::time::Duration) -> (WsClient, async_std::task::JoinHandle<()>) {
+        let (writer, reader) = ws_stream.split();
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let shared = Arc::new(WsShared{
+            writer: async_std::sync::Mutex::new(Some(writer)),
+            pending: std::sync::Mutex::new(HashMap::new()),
+            channels: std::sync::Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+            _stop_read_loop: stop_tx,
+        });
+        let read_loop = async_std::task::spawn(read_loop(reader, Arc::downgrade(&shared), stop_rx));
+
+        (WsClient{ shared: shared, timeout: timeout }, read_loop)
+    }
+
+    // True once the connection has dropped; every later call fails with LotusError::Transport.
+    pub fn is_closed(&self) -> bool {
+        self.shared.closed.load(Ordering::SeqCst)
+    }
+
+    pub async fn request(&self, method: &str, params: Vec<jsonrpsee::common::JsonValue>)
+        -> Result<jsonrpsee::common::JsonValue, LotusError>
+    {
+        let (tx, rx) = oneshot::channel();
+        let request_id = self.start_request(method, params, PendingRequest::Call(tx)).await?;
+        self.wait_for_answer(request_id, rx).await
+    }
+
+    // Calls a channel-returning method and yields every value sent on the channel, each
+    // deserialized to T, until the node closes the channel or the connection drops.
+    pub async fn subscribe<T: serde::de::DeserializeOwned>(&self, method: &str, params: Vec<jsonrpsee::common::JsonValue>)
+        -> Result<Subscription<T>, LotusError>
+    {
+        let (tx, rx) = oneshot::channel();
+        let request_id = self.start_request(method, params, PendingRequest::Subscribe(tx)).await?;
+        let (chan_id, receiver) = self.wait_for_answer(request_id, rx).await?;
+        log::debug!("WsClient::subscribe: {} is channel {}",method,chan_id);
+        Ok(Subscription{
+            receiver: receiver,
+            chan_id: chan_id,
+            request_id: request_id,
+            shared: self.shared.clone(),
+            _item: std::marker::PhantomData,
+        })
+    }
+
+    async fn start_request(&self, method: &str, params: Vec<jsonrpsee::common::JsonValue>, pending: PendingRequest)
+        -> Result<u64, LotusError>
+    {
+        if self.is_closed() {
+            return Err(LotusError::Transport("websocket connection is closed".to_string()));
+        }
+        let request_id = self.shared.next_id.fetch_add(1, Ordering::SeqCst);
+        self.shared.pending.lock().unwrap().insert(request_id, pending);
+        let request = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": request_id });
+        if let Err(e) = self.shared.send(request).await {
+            self.shared.pending.lock().unwrap().remove(&request_id);
+            return Err(e);
+        }
+        Ok(request_id)
+    }
+
+    async fn wait_for_answer<T>(&self, request_id: u64, rx: oneshot::Receiver<Result<T, LotusError>>) -> Result<T, LotusError> {
+        match async_std::future::timeout(self.timeout, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(oneshot::Canceled)) => Err(LotusError::Transport("websocket connection closed before the answer arrived".to_string())),
+            Err(_) => {
+                self.shared.pending.lock().unwrap().remove(&request_id);
+                Err(LotusError::Timeout(self.timeout))
+            }
+        }
+    }
+}
+
+impl WsShared {
+    async fn send(&self, jsonval: jsonrpsee::common::JsonValue) -> Result<(), LotusError> {
+        let mut writer_guard = self.writer.lock().await;
+        let writer = writer_guard.as_mut()
+            .ok_or_else(|| LotusError::Transport("websocket connection is closed".to_string()))?;
+        writer.send(WsMessage::Text(jsonval.to_string())).await
+            .map_err(|e| LotusError::Transport(format!("websocket send failed: {}",e)))
+    }
+
+    fn dispatch(&self, text: &str) {
+        let jsonval : jsonrpsee::common::JsonValue = match serde_json::from_str(text) {
+            Ok(jsonval) => jsonval,
+            Err(e) => {
+                log::error!("WsClient: unparseable message '{}': {}",text,e);
+                return;
+            }
+        };
+
+        // Calls from the node:  values on, and closing of, our channels
+        if let Some(method) = jsonval.get("method").and_then(|m| m.as_str()) {
+            let chan_id = jsonval.pointer("/params/0").and_then(|c| c.as_u64());
+            match (method, chan_id) {
+                ("xrpc.ch.val", Some(chan_id)) => {
+                    let value = jsonval.pointer("/params/1").cloned().unwrap_or(jsonrpsee::common::JsonValue::Null);
+                    if let Some(sender) = self.channels.lock().unwrap().get(&chan_id) {
+                        let _ = sender.unbounded_send(value);
+                    }
+                },
+                ("xrpc.ch.close", Some(chan_id)) => {
+                    log::debug!("WsClient: node closed channel {}",chan_id);
+                    self.channels.lock().unwrap().remove(&chan_id);
+                },
+                _ => {
+                    log::debug!("WsClient: ignoring call '{}' from node",method);
+                },
+            }
+            return;
+        }
+
+        // Answers to our requests
+        if let Some(request_id) = jsonval.get("id").and_then(|id| id.as_u64()) {
+            let result = match jsonval.get("error") {
+                Some(error) if !error.is_null() => Err(rpc_error_from_json(error)),
+                _ => Ok(jsonval.get("result").cloned().unwrap_or(jsonrpsee::common::JsonValue::Null)),
+            };
+            let pending = self.pending.lock().unwrap().remove(&request_id);
+            match pending {
+                Some(PendingRequest::Call(tx)) => {
+                    let _ = tx.send(result);
+                },
+                Some(PendingRequest::Subscribe(tx)) => {
+                    // Register the channel before anything else is read, so no value is lost
+                    let result = result.and_then(|chan_jsonval| {
+                        let chan_id = chan_jsonval.as_u64().ok_or_else(|| LotusError::Deserialize(
+                            format!("expected a channel id, got '{}'",chan_jsonval)))?;
+                        let (sender, receiver) = mpsc::unbounded();
+                        self.channels.lock().unwrap().insert(chan_id, sender);
+                        Ok((chan_id, receiver))
+                    });
+                    let _ = tx.send(result);
+                },
+                None => {
+                    log::debug!("WsClient: answer to unknown (or timed out) request {}",request_id);
+                },
+            }
+        }
+    }
+
+    // Fails everything still waiting and ends every subscription stream.
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.pending.lock().unwrap().clear();
+        self.channels.lock().unwrap().clear();
+    }
+}
+
+// The last client handle going away says goodbye to the node
+impl Drop for WsShared {
+    fn drop(&mut self) {
+        if let Some(mut writer) = self.writer.get_mut().take() {
+            if !self.closed.load(Ordering::SeqCst) {
+                async_std::task::spawn(async move {
+                    if let Err(e) = writer.send(WsMessage::Close(None)).await {
+                        log::debug!("WsClient: failed to send close: {}",e);
+                    }
+                    let _ = writer.close().await;
+                });
+            }
+        }
+    }
+}
+
+// Reads until the node closes the connection or `stop` fires (the last client handle was
+// dropped).  Holding only a Weak reference keeps the loop from keeping the connection alive.
+async fn read_loop(mut reader: futures::stream::SplitStream<WsStream>, shared: Weak<WsShared>, mut stop: oneshot::Receiver<()>) {
+    loop {
+        let ws_message = match futures::future::select(reader.next(), &mut stop).await {
+            futures::future::Either::Left((Some(ws_message), _)) => ws_message,
+            futures::future::Either::Left((None, _)) => break,
+            futures::future::Either::Right(_) => {
+                log::debug!("WsClient: last client dropped; closing the connection");
+                return;
+            },
+        };
+        let shared = match shared.upgrade() {
+            Some(shared) => shared,
+            None => return,
+        };
+        match ws_message {
+            Ok(WsMessage::Text(text)) => shared.dispatch(&text),
+            Ok(WsMessage::Close(frame)) => {
+                log::debug!("WsClient: node closed the connection: {:?}",frame);
+                break;
+            },
+            Ok(_) => {},
+            Err(e) => {
+                log::error!("WsClient: websocket read failed: {}",e);
+                break;
+            },
+        }
+    }
+    if let Some(shared) = shared.upgrade() {
+        shared.close();
+    }
+}
+
+fn rpc_error_from_json(error: &jsonrpsee::common::JsonValue) -> LotusError {
+    LotusError::Rpc{
+        code: error.get("code").and_then(|c| c.as_i64()).unwrap_or(0),
+        message: error.get("message").and_then(|m| m.as_str()).unwrap_or("").to_string(),
+        data: error.get("data").cloned().filter(|d| !d.is_null()),
+    }
+}
+
+// Turns an http(s) RPC endpoint url into the ws(s) url of the same endpoint.  wss:// is
+// dialed over the platform's TLS (async-tungstenite's async-native-tls feature).
+pub fn endpoint_url_to_ws_url(endpoint_url: &str) -> String {
+    if endpoint_url.starts_with("https://") {
+        format!("wss://{}",&endpoint_url["https://".len()..])
+    } else if endpoint_url.starts_with("http://") {
+        format!("ws://{}",&endpoint_url["http://".len()..])
+    } else {
+        endpoint_url.to_string()
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////
+//
+// Subscription - stream of the values sent on one Lotus channel
+//
+//////////////////////////////////////////////////////////////////////////////////////
+
+// Dropping the Subscription cancels the channel on the node.
+pub struct Subscription<T> {
+    receiver: ChannelReceiver,
+    chan_id: u64,
+    request_id: u64,
+    shared: Arc<WsShared>,
+    _item: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T: serde::de::DeserializeOwned> futures::Stream for Subscription<T> {
+    type Item = Result<T, LotusError>;
+
+    fn poll_next(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context) -> std::task::Poll<Option<Self::Item>> {
+        self.get_mut().receiver.poll_next_unpin(cx)
+            .map(|value| value.map(|jsonval| serde_json::from_value(jsonval).map_err(LotusError::from)))
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        self.shared.channels.lock().unwrap().remove(&self.chan_id);
+        if !self.shared.closed.load(Ordering::SeqCst) {
+            let shared = self.shared.clone();
+            let request_id = self.request_id;
+            async_std::task::spawn(async move {
+                let cancel = json!({ "jsonrpc": "2.0", "method": "xrpc.cancel", "params": [request_id] });
+                if let Err(e) = shared.send(cancel).await {
+                    log::debug!("Subscription::drop: failed to cancel request {}: {}",request_id,e);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_url_to_ws_url() {
+        assert_eq!(endpoint_url_to_ws_url("http://lotus1:1234/rpc/v0"), "ws://lotus1:1234/rpc/v0");
+        assert_eq!(endpoint_url_to_ws_url("https://lotus1:1234/rpc/v0"), "wss://lotus1:1234/rpc/v0");
+        assert_eq!(endpoint_url_to_ws_url("ws://lotus1:1234/rpc/v0"), "ws://lotus1:1234/rpc/v0");
+    }
+
+    #[test]
+    fn test_dropping_client_ends_read_loop() {
+        async_std::task::block_on(async {
+            // a node that accepts one websocket and reads until it closes
+            let listener = async_std::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let ws_url = format!("ws://{}/rpc/v0", listener.local_addr().unwrap());
+            let node = async_std::task::spawn(async move {
+                let (tcp_stream, _) = listener.accept().await.unwrap();
+                let mut ws_stream = async_tungstenite::accept_async(tcp_stream).await.unwrap();
+                let mut got_close = false;
+                while let Some(Ok(ws_message)) = ws_stream.next().await {
+                    got_close |= ws_message.is_close();
+                }
+                got_close
+            });
+
+            let (ws_stream, _) = async_tungstenite::async_std::connect_async(ws_url.as_str()).await.unwrap();
+            let timeout = std::time::Duration::from_secs(5);
+            let (ws_client, read_loop) = WsClient::from_stream(ws_stream, timeout);
+            let another_handle = ws_client.clone();
+            drop(ws_client);
+            assert!(!another_handle.is_closed());
+            drop(another_handle);
+
+            assert!(async_std::future::timeout(timeout, read_loop).await.is_ok(), "read loop still running");
+            assert_eq!(async_std::future::timeout(timeout, node).await, Ok(true), "node was not sent a close");
+        });
+    }
+}